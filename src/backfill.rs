@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use tracing::warn;
+
+use crate::{
+    config::{SiteConfig, SocialApi},
+    error::{Categorize, ErrorCategory},
+    post::extract_date,
+    social::{backfill_social, http_client},
+};
+
+// What `backfill` did with a post
+#[derive(Debug, PartialEq, Eq)]
+pub enum Backfilled {
+    Posted,
+    AlreadyPosted,
+}
+
+// Post the `count` most recent published posts on the `api` instances they were not posted on
+// yet, to announce them on a newly configured social network. Returns the result for each post, a
+// failure not stopping the others.
+pub async fn backfill(
+    count: usize,
+    api: SocialApi,
+    cfg: &SiteConfig,
+) -> Result<Vec<(PathBuf, Result<Backfilled>)>> {
+    let Some(social_cfg) = cfg.social.as_ref() else {
+        return Err(anyhow!("No `social` section in `emile.toml`"))
+            .categorize(ErrorCategory::Config);
+    };
+    if !social_cfg
+        .instances
+        .iter()
+        .any(|instance| instance.api == api)
+    {
+        return Err(anyhow!("No {api} instance in `emile.toml`")).categorize(ErrorCategory::Config);
+    }
+
+    let client = http_client(social_cfg).categorize(ErrorCategory::Config)?;
+    let mut results = Vec::new();
+    for post in recent_posts(count, cfg)? {
+        let content = std::fs::read_to_string(&post)?;
+        let res = match backfill_social(&client, social_cfg, &content, &post, api).await {
+            Ok(Some((new_content, nb_failed))) => {
+                std::fs::write(&post, new_content)?;
+                if nb_failed == 0 {
+                    Ok(Backfilled::Posted)
+                } else {
+                    Err(anyhow!("failed on {nb_failed} {api} instance(s)"))
+                }
+            }
+            Ok(None) => Ok(Backfilled::AlreadyPosted),
+            Err(err) => Err(err),
+        };
+        results.push((post, res));
+    }
+    Ok(results)
+}
+
+// The `count` most recent published posts, in all languages, by frontmatter date
+fn recent_posts(count: usize, cfg: &SiteConfig) -> Result<Vec<PathBuf>> {
+    let mut posts = Vec::new();
+    for dir in cfg.publish_dirs() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !cfg.is_post(&path) {
+                continue;
+            }
+            match extract_date(&path, cfg) {
+                Ok(date) => posts.push((date, path)),
+                Err(err) => warn!("Skipping `{}`: {err}", path.to_string_lossy()),
+            }
+        }
+    }
+    posts.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(posts
+        .into_iter()
+        .take(count)
+        .map(|(_, path)| path)
+        .collect())
+}
@@ -1,11 +1,16 @@
-use std::fmt::Display;
+use std::collections::HashMap;
+use std::fmt::{Display, Write as _};
 use std::fs::File;
 use std::io::{prelude::*, BufReader};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
-use anyhow::{bail, Result};
-use chrono::{FixedOffset, NaiveTime};
-use serde_derive::Deserialize;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveTime, Utc};
+use clap::ValueEnum;
+use regex::{Captures, Regex};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::post::{is_section_index, parse_front_date};
 
 #[derive(Debug)]
 pub struct SiteConfig {
@@ -15,8 +20,14 @@ pub struct SiteConfig {
     pub drafts_year_shift: i32,
     // emile will take this file to create a draft post by adding `title`, `date` and `draft = true` in the frontmatter
     pub draft_template: String,
+    // templates to use instead of `draft_template` for `new --type <name>`, by type name
+    pub draft_templates: HashMap<String, String>,
+    // directory of the draft and social templates
+    pub templates_dir: PathBuf,
     // Destination for `publish` command.
     pub publish_dest: PathBuf,
+    // destination for `publish` command by language of the post, `publish_dest` for the others
+    pub lang_publish_dest: HashMap<String, PathBuf>,
     // Schedule directory
     pub schedule_dir: PathBuf,
     // timezone in which the posts are dated, relative to UTC
@@ -25,12 +36,42 @@ pub struct SiteConfig {
     pub debouncing: u64,
     // time to use if no time given in schedule command
     pub default_sch_time: NaiveTime,
+    // set the `updated` field of the frontmatter when publishing
+    pub set_updated_on_publish: bool,
+    // what happens to the `draft` field when publishing
+    pub publish_draft_behavior: PublishDraftBehavior,
+    // `schedule` refuses dates sooner than this amount of seconds from now
+    pub min_schedule_lead_secs: u64,
+    // extensions of the posts' files
+    pub markdown_extensions: Vec<String>,
+    // maximum number of posts scheduled in the past that `watch` publishes at once
+    pub catchup_limit: Option<usize>,
+    // seconds between the publications of the past-due posts beyond `catchup_limit`, 0 leaving
+    // them for a manual release
+    pub catchup_spacing_secs: u64,
+    // posts due for longer than these seconds are not published by `watch` but left for a manual
+    // release
+    pub past_schedule_grace_secs: Option<u64>,
+    // command building the site, program then arguments
+    pub build_command: Vec<String>,
+    // `watch` publishes the drafts of `drafts_creation_dir` whose `draft` is set to false
+    pub publish_on_draft_false: bool,
+    // number of drafts kept in `BACKUP_DIR` before being moved by `schedule`, 0 keeping none
+    pub schedule_backups: usize,
+    // `schedule` sets `draft = true` in the posts it moves to `schedule_dir`
+    pub schedule_as_draft: bool,
+    // frontmatter fields a post must have to be published, as dotted keys
+    pub required_front_fields: Vec<String>,
+    // chrono format of the `date` written in the frontmatter by `new`, `publish` and `schedule`
+    pub date_format: String,
     // social media configuration
     pub social: Option<SocialCfg>,
 }
 
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize, ValueEnum,
+)]
 pub enum SocialApi {
     #[serde(alias = "mastodon")]
     Mastodon,
@@ -38,6 +79,74 @@ pub enum SocialApi {
     Bluesky,
 }
 
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkPlacement {
+    // `link_tag` in the post's body is replaced by the `link_template` snippet
+    #[default]
+    Body,
+    // links are put in `social_links` of the `[extra]` section of the frontmatter
+    Frontmatter,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkInjection {
+    // `link_tag` is replaced by the links snippet
+    #[default]
+    Replace,
+    // the links snippet is added at the end of the body, `link_tag` or not
+    Append,
+    // the links snippet is added at the start of the body, `link_tag` or not
+    Prepend,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkCheck {
+    // the link to the post is not checked
+    #[default]
+    Off,
+    // a link not answering with a success is logged, and posted anyway
+    Warn,
+    // a link not answering with a success prevents posting on social media
+    Abort,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdempotencyKey {
+    // derived from the text of the toot, an edited post is posted again
+    #[default]
+    Text,
+    // derived from the slug and the language of the post, even if its text changed
+    Slug,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishDraftBehavior {
+    // the `draft` line is removed from the frontmatter
+    #[default]
+    Remove,
+    // the frontmatter keeps `draft = false`
+    SetFalse,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagCase {
+    // `machine-learning` -> `MachineLearning`
+    #[default]
+    Pascal,
+    // `machine-learning` -> `machineLearning`
+    Camel,
+    // `machine-learning` -> `machinelearning`
+    Lower,
+    // tag as written in the frontmatter, keeping only its letters, digits and `_`
+    Original,
+}
+
 impl Display for SocialApi {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -51,20 +160,66 @@ impl Display for SocialApi {
 pub struct SocialCfg {
     // path to the template to use for posting on mastodon
     pub social_template: PathBuf,
+    // directory of the templates, same as the site's `templates_dir`
+    pub templates_dir: PathBuf,
     // default language
     pub default_lang: String,
     // base url
     pub base_url: String,
+    // path segment between base url and the post's slug
+    pub post_path: String,
+    // publish directory <-> path segment, overriding `post_path`
+    pub section_path: Option<Vec<SectionPath>>,
+    // Zola taxonomy the tags of the social post come from
+    pub tag_taxonomy: String,
     // tag <-> language
     pub tag_lang: Option<Vec<TagLang>>,
+    // tag <-> social template, overriding `social_template`
+    pub tag_template: Vec<TagTemplate>,
+    // frontmatter key holding the language of the post, taking precedence over `tag_lang`
+    pub lang_key: Option<String>,
     // tags to not put in the toot
     pub filtered_tag: Vec<String>,
+    // casing applied to tags when turned into hashtags
+    pub tag_case: TagCase,
     // path to the template for the link to the social post
     pub link_template: PathBuf,
     // tag to replace with expanded link_temolate
     pub link_tag: String,
+    // where the links to the social posts are put in the blog post
+    pub link_placement: LinkPlacement,
+    // where the links snippet goes in the body, with the `body` placement
+    pub link_injection: LinkInjection,
+    // append the link to the post to the social posts whose template doesn't put it
+    pub always_include_link: bool,
+    // line appended to every social post
+    pub social_signature: Option<String>,
+    // `social_signature` by language of the social post
+    pub lang_social_signature: HashMap<String, String>,
+    // whether the link to the post is checked with a HEAD request before posting
+    pub check_link: LinkCheck,
+    // what Mastodon's Idempotency-Key is derived from
+    pub idempotency_key: IdempotencyKey,
+    // proxy for the requests to the social servers
+    pub http_proxy: Option<String>,
+    // PEM certificate to trust in addition to the system's ones
+    pub extra_ca_cert: Option<PathBuf>,
     // social server to post to
     pub instances: Vec<SocialInstance>,
+    // only log what would be sent to the social servers, set by `--no-network`
+    #[serde(skip)]
+    pub offline: bool,
+}
+
+impl SocialCfg {
+    // Signature of the social posts in `lang`
+    pub fn signature_for(&self, lang: &str) -> Option<&str> {
+        self.lang_social_signature
+            .get(lang)
+            .or(self.social_signature.as_ref())
+            .map(String::as_str)
+            .filter(|signature| !signature.trim().is_empty())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -74,9 +229,42 @@ pub struct SocialInstance {
     // which social network API to use
     pub api: SocialApi,
     // env var to read access token from
+    #[serde(default)]
     pub token_var: String,
+    // file to read access token (or Bluesky's app password) from, instead of `token_var`
+    pub token_file: Option<PathBuf>,
     // env var to read user’s id from
     pub handle_var: Option<String>,
+    // languages to post in, one status each. The post's language is the primary one if listed,
+    // else the first one. Empty means the post's language only
+    #[serde(default)]
+    pub langs: Vec<String>,
+    // times a request rate limited by the server is sent again, after the delay it asks for
+    #[serde(default)]
+    pub max_retries: u32,
+    // Bluesky only: host of the PDS the API calls are sent to, `server` if not set
+    pub pds_host: Option<String>,
+    // Bluesky only: base of the links to the posts, `https://bsky.app` if not set
+    pub app_url: Option<String>,
+}
+
+impl SocialInstance {
+    // Access token (or app password) of the instance, from `token_file` if set, else `token_var`
+    pub fn token(&self) -> Result<String> {
+        if let Some(file) = &self.token_file {
+            let token = std::fs::read_to_string(file)
+                .with_context(|| format!("Failed to read `{}`", file.to_string_lossy()))?;
+            let token = token.trim();
+            if token.is_empty() {
+                bail!("`{}` is empty", file.to_string_lossy());
+            }
+            return Ok(token.to_string());
+        }
+        match std::env::var(&self.token_var) {
+            Ok(token) => Ok(token),
+            Err(_) => bail!("`{}` env var is not defined", self.token_var),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -85,22 +273,103 @@ pub struct TagLang {
     pub lang: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct TagTemplate {
+    pub tag: String,
+    pub template: PathBuf,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SectionPath {
+    pub dir: PathBuf,
+    pub path: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SocialCfgBuilder {
     // template to use for posting on mastodon
     pub social_template: Option<PathBuf>,
+    // url of the website, overriding Zola's `base_url`
+    pub base_url: Option<String>,
+    // path segment between base url and the post's slug
+    pub post_path: Option<String>,
+    // publish directory <-> path segment, overriding `post_path`
+    pub section_path: Option<Vec<SectionPath>>,
+    // Zola taxonomy the tags of the social post come from ("tags" by default)
+    pub tag_taxonomy: Option<String>,
     // tag <-> language
     pub tag_lang: Option<Vec<TagLang>>,
+    // tag <-> social template, overriding `social_template`. The first matching one is used
+    #[serde(default)]
+    pub tag_template: Vec<TagTemplate>,
+    // frontmatter key holding the language of the post, taking precedence over `tag_lang`. Keys
+    // of tables are separated by dots (ex: "extra.lang")
+    pub lang_key: Option<String>,
     // tags to not put in the toot
     pub filtered_tag: Vec<String>,
+    // casing applied to tags when turned into hashtags
+    pub tag_case: Option<TagCase>,
     // path to the template for the link to the social post
     pub link_template: Option<PathBuf>,
     // tag to replace with expanded link_temolate
     pub link_tag: Option<String>,
+    // where the links to the social posts are put in the blog post ("body" by default)
+    pub link_placement: Option<LinkPlacement>,
+    // where the links snippet goes in the body, with the `body` placement ("replace" by default)
+    pub link_injection: Option<LinkInjection>,
+    // append the link to the post to the social posts whose template doesn't put it (false by
+    // default)
+    pub always_include_link: Option<bool>,
+    // line appended to every social post, after the link added by `always_include_link` (none by
+    // default)
+    pub social_signature: Option<String>,
+    // `social_signature` by language of the social post, `social_signature` for the others
+    pub lang_social_signature: Option<HashMap<String, String>>,
+    // whether the link to the post is checked with a HEAD request before posting: "off"
+    // (default), "warn" or "abort"
+    pub check_link: Option<LinkCheck>,
+    // what Mastodon's Idempotency-Key is derived from: "text" (default) or "slug"
+    pub idempotency_key: Option<IdempotencyKey>,
+    // proxy for the requests to the social servers, instead of the `HTTPS_PROXY` env var
+    pub http_proxy: Option<String>,
+    // PEM certificate to trust in addition to the system's ones
+    pub extra_ca_cert: Option<PathBuf>,
     // social server to post to
     pub instances: Vec<SocialInstance>,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TimezoneCfg {
+    Hours(i32),
+    Offset(String),
+}
+
+impl TimezoneCfg {
+    fn to_offset(&self) -> Result<FixedOffset> {
+        let offset = match self {
+            TimezoneCfg::Hours(hours) => hours.checked_mul(3600).and_then(FixedOffset::east_opt),
+            TimezoneCfg::Offset(offset) => offset.parse::<FixedOffset>().ok(),
+        };
+        match offset {
+            Some(offset) => Ok(offset),
+            None => bail!(
+                "Invalid `timezone` value `{}`, expected hours between -23 and 23 or an offset like \"+05:30\"",
+                self
+            ),
+        }
+    }
+}
+
+impl Display for TimezoneCfg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimezoneCfg::Hours(hours) => write!(f, "{hours}"),
+            TimezoneCfg::Offset(offset) => write!(f, "{offset}"),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SiteConfigBuilder {
     // drafts created with `new` command will end here. Path relative to root of the blog.
@@ -109,20 +378,149 @@ pub struct SiteConfigBuilder {
     pub drafts_year_shift: Option<i32>,
     // emile will take this file to create a draft post by adding `title`, `date` and `draft = true` in the frontmatter
     pub draft_template: Option<String>,
+    // templates to use instead of `draft_template` for `new --type <name>`, by type name
+    pub draft_templates: Option<HashMap<String, String>>,
+    // directory of the draft and social templates ("./templates/" by default)
+    pub templates_dir: Option<PathBuf>,
     // Destination for `publish` command.
     pub publish_dest: Option<PathBuf>,
+    // destination for `publish` command by language of the post, `publish_dest` for the others
+    // (empty by default)
+    pub lang_publish_dest: Option<HashMap<String, PathBuf>>,
     // Schedule directory
     pub schedule_dir: Option<PathBuf>,
-    // timezone in which the posts are dated, relative to UTC
-    pub timezone: Option<i32>,
+    // timezone in which the posts are dated, relative to UTC, in hours or as "+05:30"
+    pub timezone: Option<TimezoneCfg>,
     // how long (in seconds) to wait for end of filesystem event (10s by default)
     pub debouncing: Option<u64>,
     // time to use if no time given in schedule command
     pub default_sch_time: Option<NaiveTime>,
+    // set the `updated` field of the frontmatter when publishing (false by default)
+    pub set_updated_on_publish: Option<bool>,
+    // what happens to the `draft` field when publishing (`remove` by default)
+    pub publish_draft_behavior: Option<PublishDraftBehavior>,
+    // `schedule` refuses dates sooner than this amount of seconds from now (0 by default, only
+    // refusing dates in the past)
+    pub min_schedule_lead_secs: Option<u64>,
+    // extensions of the posts' files (["md"] by default)
+    pub markdown_extensions: Option<Vec<String>>,
+    // maximum number of posts scheduled in the past that `watch` publishes at once (no limit by
+    // default)
+    pub catchup_limit: Option<usize>,
+    // seconds between the publications of the past-due posts beyond `catchup_limit` (0 by
+    // default, leaving them for a manual release)
+    pub catchup_spacing_secs: Option<u64>,
+    // posts due for longer than these seconds are not published by `watch` but left for a manual
+    // release (no limit by default)
+    pub past_schedule_grace_secs: Option<u64>,
+    // command building the site, program then arguments (["zola", "build"] by default)
+    pub build_command: Option<Vec<String>>,
+    // `watch` publishes the drafts of `drafts_creation_dir` whose `draft` is set to false (false
+    // by default)
+    pub publish_on_draft_false: Option<bool>,
+    // number of drafts kept in `.emile-backups/` before being moved by `schedule` (0 by default,
+    // keeping none)
+    pub schedule_backups: Option<usize>,
+    // `schedule` sets `draft = true` in the posts it moves to `schedule_dir`, so that Zola doesn't
+    // build them before their date (false by default)
+    pub schedule_as_draft: Option<bool>,
+    // frontmatter fields a post must have, not empty, to be published or scheduled, as dotted
+    // keys like "taxonomies.tags" (none by default)
+    pub required_front_fields: Option<Vec<String>>,
+    // chrono format of the `date` written in the frontmatter, "%Y-%m-%d" or RFC3339
+    // ("%Y-%m-%dT%H:%M:%S%:z" by default)
+    pub date_format: Option<String>,
     // social media configuration
     pub social: Option<SocialCfgBuilder>,
 }
 
+impl SiteConfig {
+    // Whether `path` has one of the `markdown_extensions`
+    pub fn is_markdown(&self, path: &Path) -> bool {
+        path.extension()
+            .map(|ext| {
+                let ext = ext.to_string_lossy().to_lowercase();
+                self.markdown_extensions
+                    .iter()
+                    .any(|md_ext| md_ext.trim_start_matches('.').to_lowercase() == ext)
+            })
+            .unwrap_or(false)
+    }
+
+    // Whether `path` is a markdown post, excluding the `_index` of sections
+    pub fn is_post(&self, path: &Path) -> bool {
+        self.is_markdown(path) && path.file_stem().is_some() && !is_section_index(path)
+    }
+
+    // Look for the draft and social templates in `dir` instead of `templates_dir`
+    pub fn override_templates_dir(&mut self, dir: PathBuf) {
+        if let Some(social) = self.social.as_mut() {
+            social.templates_dir = dir.clone();
+        }
+        self.templates_dir = dir;
+    }
+
+    // `date` as written in the frontmatter, following `date_format`
+    pub fn front_date(&self, date: &DateTime<FixedOffset>) -> String {
+        date.format(&self.date_format).to_string()
+    }
+
+    // Never contact the social servers, only log what would be sent to them
+    pub fn set_offline(&mut self) {
+        if let Some(social) = self.social.as_mut() {
+            social.offline = true;
+        }
+    }
+
+    // Destination of a post in `lang`
+    pub fn publish_dest_for(&self, lang: Option<&str>) -> &Path {
+        lang.and_then(|lang| self.lang_publish_dest.get(lang))
+            .unwrap_or(&self.publish_dest)
+    }
+
+    // All the directories posts are published to
+    pub fn publish_dirs(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.publish_dest.as_path())
+            .chain(self.lang_publish_dest.values().map(PathBuf::as_path))
+    }
+
+    // Publish into `dest` instead of `publish_dest`, whatever the language of the post. Unless
+    // `section_path` already covers it, the social link uses the path of `dest` inside `content`,
+    // as Zola does for sections.
+    pub fn override_publish_dest(&mut self, dest: PathBuf) {
+        if let Some(social) = self.social.as_mut() {
+            let sections = social.section_path.get_or_insert_with(Vec::new);
+            if !sections.iter().any(|section| section.dir == dest) {
+                let rel = dest
+                    .strip_prefix("content")
+                    .or_else(|_| dest.strip_prefix("./content"))
+                    .unwrap_or(&dest);
+                let path = rel
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                sections.push(SectionPath {
+                    dir: dest.clone(),
+                    path,
+                });
+            }
+        }
+        self.publish_dest = dest;
+        self.lang_publish_dest.clear();
+    }
+
+    // Link the social posts to `base_url` instead of the site's URL, a staging copy for example
+    pub fn override_base_url(&mut self, base_url: &str) -> Result<()> {
+        let Some(social) = self.social.as_mut() else {
+            bail!("`--base-url` needs the `social` section of `emile.toml`");
+        };
+        SiteConfigBuilder::check_base_url(base_url)?;
+        social.base_url = base_url.to_owned();
+        Ok(())
+    }
+}
+
 impl SiteConfigBuilder {
     // to be run from the website's directory
     pub fn get_config() -> SiteConfig {
@@ -177,31 +575,205 @@ impl SiteConfigBuilder {
         }
     }
 
+    // Expand `${VAR}` references in every string value of the config
+    fn interpolate_env(value: &mut toml::Value) -> Result<()> {
+        match value {
+            toml::Value::String(s) => {
+                let reg = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+                let mut missing = None;
+                let expanded = reg.replace_all(s, |caps: &Captures| {
+                    std::env::var(&caps[1]).unwrap_or_else(|_| {
+                        missing.get_or_insert_with(|| caps[1].to_owned());
+                        String::new()
+                    })
+                });
+                if let Some(var) = missing {
+                    bail!("`{var}` env var referenced in `emile.toml` is not defined");
+                }
+                *s = expanded.into_owned();
+            }
+            toml::Value::Array(values) => {
+                for v in values {
+                    SiteConfigBuilder::interpolate_env(v)?;
+                }
+            }
+            toml::Value::Table(table) => {
+                for (_, v) in table.iter_mut() {
+                    SiteConfigBuilder::interpolate_env(v)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Links in social posts need an absolute url to the website
+    fn check_base_url(base_url: &str) -> Result<()> {
+        match reqwest::Url::parse(base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            _ => bail!(
+                "`base_url` is `{base_url}`, which is not an absolute url: set it in Zola's \
+                 `config.toml` or in the `[social]` section of `emile.toml`"
+            ),
+        }
+    }
+
+    // The dates written with `date_format` must be read back the same by `extract_date`, or the
+    // scheduled posts would be published at the wrong time
+    fn check_date_format(date_format: &str, timezone: FixedOffset) -> Result<()> {
+        let date = Utc::now().with_timezone(&timezone);
+        let mut formatted = String::new();
+        if write!(formatted, "{}", date.format(date_format)).is_err() {
+            bail!("Invalid `date_format` `{date_format}`");
+        }
+        match parse_front_date(&formatted, timezone) {
+            Ok(parsed) if parsed.format(date_format).to_string() == formatted => Ok(()),
+            _ => bail!(
+                "`date_format` `{date_format}` gives dates like `{formatted}`, expected \"%Y-%m-%d\" \
+                 or an RFC3339 format like \"%Y-%m-%dT%H:%M:%S%:z\""
+            ),
+        }
+    }
+
+    // Expand `~` in the paths of the config, which can't be left to a shell
+    fn expand_paths(&mut self, home: Option<&Path>) -> Result<()> {
+        let expand = |field: &str, path: &mut PathBuf| -> Result<()> {
+            *path = expand_path(path, home).with_context(|| format!("Invalid `{field}`"))?;
+            Ok(())
+        };
+        for (field, path) in [
+            ("drafts_creation_dir", &mut self.drafts_creation_dir),
+            ("templates_dir", &mut self.templates_dir),
+            ("publish_dest", &mut self.publish_dest),
+            ("schedule_dir", &mut self.schedule_dir),
+        ] {
+            if let Some(path) = path {
+                expand(field, path)?;
+            }
+        }
+        for dir in self
+            .lang_publish_dest
+            .iter_mut()
+            .flat_map(|dests| dests.values_mut())
+        {
+            expand("lang_publish_dest", dir)?;
+        }
+        if let Some(social) = self.social.as_mut() {
+            for (field, path) in [
+                ("social_template", &mut social.social_template),
+                ("link_template", &mut social.link_template),
+                ("extra_ca_cert", &mut social.extra_ca_cert),
+            ] {
+                if let Some(path) = path {
+                    expand(field, path)?;
+                }
+            }
+            for section in social.section_path.iter_mut().flatten() {
+                expand("section_path", &mut section.dir)?;
+            }
+            for tag_template in &mut social.tag_template {
+                expand("tag_template", &mut tag_template.template)?;
+            }
+            for file in social
+                .instances
+                .iter_mut()
+                .filter_map(|instance| instance.token_file.as_mut())
+            {
+                expand("token_file", file)?;
+            }
+        }
+        Ok(())
+    }
+
     fn parse(s: &str) -> Result<SiteConfig> {
-        let cfg_builder: SiteConfigBuilder = toml::from_str(s)?;
+        let mut value: toml::Value = toml::from_str(s)?;
+        SiteConfigBuilder::interpolate_env(&mut value)
+            .with_context(|| "Failed to expand env vars in config")?;
+        let mut cfg_builder: SiteConfigBuilder = value.try_into()?;
+        cfg_builder.expand_paths(home_dir().as_deref())?;
         let (default_lang, base_url) = SiteConfigBuilder::get_config_from_zola();
 
-        let social = cfg_builder.social.map(|cfg_builder| SocialCfg {
+        let lang_publish_dest = cfg_builder.lang_publish_dest.unwrap_or_default();
+        let build_command = cfg_builder
+            .build_command
+            .unwrap_or_else(default_build_command);
+        if build_command.is_empty() {
+            bail!("`build_command` can't be empty");
+        }
+        let timezone = cfg_builder
+            .timezone
+            .map(|t| t.to_offset())
+            .transpose()?
+            .unwrap_or(FixedOffset::east_opt(0).unwrap());
+        let date_format = cfg_builder.date_format.unwrap_or_else(default_date_format);
+        SiteConfigBuilder::check_date_format(&date_format, timezone)?;
+        let templates_dir = cfg_builder
+            .templates_dir
+            .unwrap_or_else(|| PathBuf::from("./templates/"));
+        let mut social = cfg_builder.social.map(|cfg_builder| SocialCfg {
             social_template: cfg_builder
                 .social_template
                 .unwrap_or_else(|| PathBuf::from("social.txt")),
+            templates_dir: templates_dir.clone(),
             default_lang,
-            base_url,
+            base_url: cfg_builder.base_url.unwrap_or(base_url),
+            post_path: cfg_builder.post_path.unwrap_or_else(|| "posts".to_owned()),
+            section_path: cfg_builder.section_path,
+            tag_taxonomy: cfg_builder
+                .tag_taxonomy
+                .unwrap_or_else(|| "tags".to_owned()),
             tag_lang: cfg_builder.tag_lang,
+            tag_template: cfg_builder.tag_template,
+            lang_key: cfg_builder.lang_key,
             filtered_tag: cfg_builder.filtered_tag,
+            tag_case: cfg_builder.tag_case.unwrap_or_default(),
             link_template: cfg_builder
                 .link_template
                 .unwrap_or_else(|| PathBuf::from("social_link.txt")),
             link_tag: cfg_builder
                 .link_tag
                 .unwrap_or("{$ emile_social $}".to_owned()),
+            link_placement: cfg_builder.link_placement.unwrap_or_default(),
+            link_injection: cfg_builder.link_injection.unwrap_or_default(),
+            always_include_link: cfg_builder.always_include_link.unwrap_or(false),
+            social_signature: cfg_builder.social_signature,
+            lang_social_signature: cfg_builder.lang_social_signature.unwrap_or_default(),
+            check_link: cfg_builder.check_link.unwrap_or_default(),
+            idempotency_key: cfg_builder.idempotency_key.unwrap_or_default(),
+            http_proxy: cfg_builder.http_proxy,
+            extra_ca_cert: cfg_builder.extra_ca_cert,
             instances: cfg_builder.instances,
+            offline: false,
         });
 
+        if let Some(social) = social.as_mut() {
+            // Zola prefixes the URLs of the other languages with their code
+            let sections = social.section_path.get_or_insert_with(Vec::new);
+            for (lang, dir) in &lang_publish_dest {
+                if !sections.iter().any(|section| &section.dir == dir) {
+                    sections.push(SectionPath {
+                        dir: dir.clone(),
+                        path: format!("{lang}/{}", social.post_path.trim_matches('/')),
+                    });
+                }
+            }
+        }
+
         if let Some(social) = &social {
             if social.instances.is_empty() {
                 bail!("No social servers defined.")
             }
+            if let Some(instance) = social
+                .instances
+                .iter()
+                .find(|instance| instance.token_var.is_empty() && instance.token_file.is_none())
+            {
+                bail!(
+                    "`{}` needs a `token_var` or a `token_file`",
+                    instance.server
+                );
+            }
+            SiteConfigBuilder::check_base_url(&social.base_url)?;
         }
 
         let config = SiteConfig {
@@ -212,23 +784,35 @@ impl SiteConfigBuilder {
             draft_template: cfg_builder
                 .draft_template
                 .unwrap_or_else(|| "draft.txt".to_string()),
+            draft_templates: cfg_builder.draft_templates.unwrap_or_default(),
+            templates_dir,
             publish_dest: cfg_builder
                 .publish_dest
                 .unwrap_or_else(|| PathBuf::from("content/posts")),
+            lang_publish_dest,
             schedule_dir: cfg_builder
                 .schedule_dir
                 .unwrap_or_else(|| PathBuf::from("content/drafts/scheduled")),
-            timezone: cfg_builder
-                .timezone
-                .map(|t| {
-                    FixedOffset::east_opt(t * 3600)
-                        .unwrap_or_else(|| panic!("Error constructing FixedOffset with {t}"))
-                })
-                .unwrap_or(FixedOffset::east_opt(0).unwrap()),
+            timezone,
             debouncing: cfg_builder.debouncing.unwrap_or(2),
             default_sch_time: cfg_builder
                 .default_sch_time
                 .unwrap_or_else(|| NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+            set_updated_on_publish: cfg_builder.set_updated_on_publish.unwrap_or(false),
+            publish_draft_behavior: cfg_builder.publish_draft_behavior.unwrap_or_default(),
+            min_schedule_lead_secs: cfg_builder.min_schedule_lead_secs.unwrap_or(0),
+            markdown_extensions: cfg_builder
+                .markdown_extensions
+                .unwrap_or_else(|| vec!["md".to_string()]),
+            catchup_limit: cfg_builder.catchup_limit,
+            catchup_spacing_secs: cfg_builder.catchup_spacing_secs.unwrap_or(0),
+            past_schedule_grace_secs: cfg_builder.past_schedule_grace_secs,
+            build_command,
+            publish_on_draft_false: cfg_builder.publish_on_draft_false.unwrap_or(false),
+            schedule_backups: cfg_builder.schedule_backups.unwrap_or(0),
+            schedule_as_draft: cfg_builder.schedule_as_draft.unwrap_or(false),
+            required_front_fields: cfg_builder.required_front_fields.unwrap_or_default(),
+            date_format,
             social,
         };
 
@@ -236,18 +820,216 @@ impl SiteConfigBuilder {
     }
 }
 
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+// `expand_path` with the home of the current user, for the paths given on the command line
+pub fn expand_cli_path(path: &Path) -> Result<PathBuf> {
+    expand_path(path, home_dir().as_deref())
+}
+
+// Replace a leading `~` by `home` and remove the `.` and `..` components, so the same directory
+// is always written the same way. An existing path is canonicalized, following the symlinks; one
+// not created yet stays relative to the website's directory if it is relative
+fn expand_path(path: &Path, home: Option<&Path>) -> Result<PathBuf> {
+    let mut components = path.components().peekable();
+    let mut expanded = PathBuf::new();
+    if let Some(Component::Normal(first)) = components.peek() {
+        let first = first.to_string_lossy();
+        if first == "~" {
+            let Some(home) = home else {
+                bail!(
+                    "Can't expand `~` in `{}`: HOME env var is not set",
+                    path.display()
+                );
+            };
+            expanded.push(home);
+            components.next();
+        } else if first.starts_with('~') {
+            bail!(
+                "Can't expand `{first}` in `{}`: only `~` is supported, for the current user's \
+                 home",
+                path.display()
+            );
+        }
+    }
+    for component in components {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    expanded.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                expanded.pop();
+            }
+            component => expanded.push(component),
+        }
+    }
+    if expanded.as_os_str().is_empty() {
+        expanded.push(".");
+    }
+    if !expanded.exists() {
+        return Ok(expanded);
+    }
+    expanded
+        .canonicalize()
+        .with_context(|| format!("Can't resolve `{}`", expanded.display()))
+}
+
+fn default_build_command() -> Vec<String> {
+    vec!["zola".to_string(), "build".to_string()]
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%dT%H:%M:%S%:z".to_string()
+}
+
 impl Default for SiteConfig {
     fn default() -> Self {
         SiteConfig {
             drafts_creation_dir: PathBuf::from("content/drafts"),
             drafts_year_shift: 0,
             draft_template: "draft.html".to_string(),
+            draft_templates: HashMap::new(),
+            templates_dir: PathBuf::from("./templates/"),
             publish_dest: PathBuf::from("content/posts"),
+            lang_publish_dest: HashMap::new(),
             schedule_dir: PathBuf::from("content/drafts/schedule"),
             timezone: FixedOffset::east_opt(0).unwrap(),
             debouncing: 2,
             default_sch_time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            set_updated_on_publish: false,
+            publish_draft_behavior: PublishDraftBehavior::Remove,
+            min_schedule_lead_secs: 0,
+            markdown_extensions: vec!["md".to_string()],
+            catchup_limit: None,
+            catchup_spacing_secs: 0,
+            past_schedule_grace_secs: None,
+            build_command: default_build_command(),
+            publish_on_draft_false: false,
+            schedule_backups: 0,
+            schedule_as_draft: false,
+            required_front_fields: Vec::new(),
+            date_format: default_date_format(),
             social: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use chrono::{DateTime, FixedOffset};
+
+    use crate::test_dir;
+
+    use super::{expand_path, SiteConfigBuilder};
+
+    #[test]
+    fn test_build_command() {
+        let cfg = SiteConfigBuilder::parse("").unwrap();
+        assert_eq!(cfg.build_command, ["zola", "build"]);
+        let cfg = SiteConfigBuilder::parse("build_command = [\"make\", \"build\"]").unwrap();
+        assert_eq!(cfg.build_command, ["make", "build"]);
+        assert!(SiteConfigBuilder::parse("build_command = []").is_err());
+    }
+
+    #[test]
+    fn test_date_format() {
+        let cfg = SiteConfigBuilder::parse("").unwrap();
+        assert_eq!(cfg.date_format, "%Y-%m-%dT%H:%M:%S%:z");
+        let cfg = SiteConfigBuilder::parse("date_format = \"%Y-%m-%d\"").unwrap();
+        let date = DateTime::parse_from_rfc3339("2024-03-05T23:30:00+01:00").unwrap();
+        assert_eq!(cfg.front_date(&date), "2024-03-05");
+        // not read back by `extract_date`
+        assert!(SiteConfigBuilder::parse("date_format = \"%d/%m/%Y\"").is_err());
+        assert!(SiteConfigBuilder::parse("date_format = \"%Q\"").is_err());
+    }
+
+    #[test]
+    fn test_timezone_hours() {
+        let cfg = SiteConfigBuilder::parse("timezone = -5").unwrap();
+        assert_eq!(cfg.timezone, FixedOffset::west_opt(5 * 3600).unwrap());
+    }
+
+    #[test]
+    fn test_timezone_half_hour() {
+        let cfg = SiteConfigBuilder::parse("timezone = \"+05:30\"").unwrap();
+        assert_eq!(
+            cfg.timezone,
+            FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap()
+        );
+        let cfg = SiteConfigBuilder::parse("timezone = \"+05:45\"").unwrap();
+        assert_eq!(
+            cfg.timezone,
+            FixedOffset::east_opt(5 * 3600 + 45 * 60).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_timezone_invalid() {
+        assert!(SiteConfigBuilder::parse("timezone = 30").is_err());
+        assert!(SiteConfigBuilder::parse("timezone = \"+25:00\"").is_err());
+        assert!(SiteConfigBuilder::parse("timezone = \"India\"").is_err());
+    }
+
+    #[test]
+    fn test_base_url() {
+        assert!(SiteConfigBuilder::check_base_url("https://example.com").is_ok());
+        assert!(SiteConfigBuilder::check_base_url("http://localhost:1111").is_ok());
+        assert!(SiteConfigBuilder::check_base_url("localhost").is_err());
+        assert!(SiteConfigBuilder::check_base_url("/blog").is_err());
+        assert!(SiteConfigBuilder::check_base_url("ftp://example.com").is_err());
+
+        // the tests run without Zola's `config.toml`: its `base_url` is missing
+        let social = "[social]\nfiltered_tag = []\ninstances = [{ server = \"mastodon.social\", api = \"mastodon\", \
+                      token_var = \"EMILE_MASTODON_TOKEN\" }]\n";
+        let err = SiteConfigBuilder::parse(social).unwrap_err();
+        assert!(err.to_string().contains("`base_url` is `localhost`"));
+        let with_url = format!("{social}base_url = \"https://example.com\"\n");
+        let cfg = SiteConfigBuilder::parse(&with_url).unwrap();
+        assert_eq!(cfg.social.unwrap().base_url, "https://example.com");
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let home = Some(Path::new("/home/me"));
+        let expand = |path: &str| expand_path(Path::new(path), home).unwrap();
+        assert_eq!(
+            expand("~/blog/drafts"),
+            PathBuf::from("/home/me/blog/drafts")
+        );
+        assert_eq!(expand("~"), PathBuf::from("/home/me"));
+        assert_eq!(expand("./content/drafts/"), PathBuf::from("content/drafts"));
+        assert_eq!(
+            expand("content/posts/../drafts"),
+            PathBuf::from("content/drafts")
+        );
+        assert_eq!(
+            expand("../shared/templates"),
+            PathBuf::from("../shared/templates")
+        );
+        // existing paths are canonicalized
+        let cur_dir = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert_eq!(expand("."), cur_dir);
+        let dir = test_dir();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("drafts")).unwrap();
+        let drafts = format!("{}/posts/../drafts/", dir.path().display());
+        assert_eq!(expand(&drafts), root.join("drafts"));
+        // not a home directory
+        assert_eq!(expand("content/~"), PathBuf::from("content/~"));
+        assert!(expand_path(Path::new("~/blog"), None).is_err());
+        assert!(expand_path(Path::new("~other/blog"), home).is_err());
+
+        let cfg = SiteConfigBuilder::parse("publish_dest = \"./content/blog/\"").unwrap();
+        assert_eq!(cfg.publish_dest, PathBuf::from("content/blog"));
+    }
+}
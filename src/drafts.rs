@@ -0,0 +1,138 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Months, Utc};
+
+use crate::{
+    config::SiteConfig,
+    post::{extract_date, frontmatter},
+};
+
+#[derive(Debug)]
+struct Draft {
+    path: PathBuf,
+    title: Option<String>,
+    // without the `drafts_year_shift` added by `new`, `None` if it can't be parsed
+    date: Option<DateTime<FixedOffset>>,
+}
+
+fn draft_title(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let front = toml::from_str::<toml::Table>(&frontmatter(&content).ok()?).ok()?;
+    front.get("title")?.as_str().map(str::to_owned)
+}
+
+// Drafts of `drafts_creation_dir`, oldest first, the ones without a valid date last
+fn read_drafts(cfg: &SiteConfig) -> Result<Vec<Draft>> {
+    let months = Months::new(cfg.drafts_year_shift.unsigned_abs() * 12);
+    let mut drafts = Vec::new();
+    for entry in std::fs::read_dir(&cfg.drafts_creation_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || !cfg.is_post(&path) {
+            continue;
+        }
+        let date = extract_date(&path, cfg).ok().and_then(|date| {
+            if cfg.drafts_year_shift >= 0 {
+                date.checked_sub_months(months)
+            } else {
+                date.checked_add_months(months)
+            }
+        });
+        drafts.push(Draft {
+            title: draft_title(&path),
+            path,
+            date,
+        });
+    }
+    drafts.sort_by(|a, b| match (a.date, b.date) {
+        (Some(a_date), Some(b_date)) => a_date.cmp(&b_date).then_with(|| a.path.cmp(&b.path)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.path.cmp(&b.path),
+    });
+    Ok(drafts)
+}
+
+fn format_age(date: &DateTime<FixedOffset>, now: &DateTime<Utc>) -> String {
+    match (*now - date.to_utc()).num_days() {
+        days if days < 0 => "in the future".to_string(),
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        days => format!("{days} days ago"),
+    }
+}
+
+pub fn list_drafts(cfg: &SiteConfig) -> Result<()> {
+    let drafts = read_drafts(cfg)?;
+    if drafts.is_empty() {
+        println!(
+            "No draft in `{}`",
+            cfg.drafts_creation_dir.to_string_lossy()
+        );
+        return Ok(());
+    }
+    let now = Utc::now();
+    for draft in &drafts {
+        let age = match &draft.date {
+            Some(date) => format_age(date, &now),
+            None => "no valid date".to_string(),
+        };
+        println!(
+            "{age:>14}  {}  ({})",
+            draft.title.as_deref().unwrap_or("<no title>"),
+            draft.path.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::{format_age, read_drafts};
+
+    #[test]
+    fn test_read_drafts() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            drafts_creation_dir: root.to_path_buf(),
+            drafts_year_shift: 1,
+            ..Default::default()
+        };
+        std::fs::create_dir_all(root.join("scheduled")).unwrap();
+        for (name, front) in [
+            ("recent.md", "title = \"Recent\"\ndate = 2025-06-01"),
+            ("old.md", "title = \"Old\"\ndate = 2024-01-01"),
+            ("broken.md", "title = \"Broken\"\ndate = \"someday\""),
+        ] {
+            std::fs::write(root.join(name), format!("+++\n{front}\n+++\n")).unwrap();
+        }
+
+        let drafts = read_drafts(&cfg).unwrap();
+        let titles: Vec<_> = drafts
+            .iter()
+            .map(|draft| draft.title.as_deref().unwrap())
+            .collect();
+        assert_eq!(titles, ["Old", "Recent", "Broken"]);
+        // the year added by `new` is removed
+        assert_eq!(
+            drafts[0].date.unwrap().format("%Y-%m-%d").to_string(),
+            "2023-01-01"
+        );
+        assert!(drafts[2].date.is_none());
+    }
+
+    #[test]
+    fn test_format_age() {
+        let now = "2024-06-10T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let age = |date: &str| format_age(&DateTime::parse_from_rfc3339(date).unwrap(), &now);
+        assert_eq!(age("2024-06-10T08:00:00+00:00"), "today");
+        assert_eq!(age("2024-06-09T08:00:00+00:00"), "1 day ago");
+        assert_eq!(age("2024-05-01T12:00:00+00:00"), "40 days ago");
+        assert_eq!(age("2024-07-01T12:00:00+00:00"), "in the future");
+    }
+}
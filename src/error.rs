@@ -0,0 +1,98 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+
+// What kind of failure an error is, for the CLI to exit with a code scripts can react to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCategory {
+    // invalid setup: not a Zola site, missing section in `emile.toml`…
+    Config,
+    // social server unreachable or refusing a post
+    Social,
+    // file that can't be read, written or moved
+    Fs,
+}
+
+impl ErrorCategory {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Config => 2,
+            ErrorCategory::Social => 3,
+            ErrorCategory::Fs => 4,
+        }
+    }
+
+    // The category given with `categorize` to the error or one of its causes, else deduced from
+    // the type of its causes
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        err.chain().find_map(|cause| {
+            if let Some(categorized) = cause.downcast_ref::<Categorized>() {
+                Some(categorized.category)
+            } else if cause.is::<reqwest::Error>() {
+                Some(ErrorCategory::Social)
+            } else if cause.is::<std::io::Error>() {
+                Some(ErrorCategory::Fs)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+// Tags an error with its category, without changing how it is displayed
+#[derive(Debug)]
+struct Categorized {
+    category: ErrorCategory,
+    error: anyhow::Error,
+}
+
+impl Display for Categorized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for Categorized {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+pub trait Categorize<T> {
+    fn categorize(self, category: ErrorCategory) -> Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> Categorize<T> for Result<T, E> {
+    fn categorize(self, category: ErrorCategory) -> Result<T> {
+        self.map_err(|err| {
+            anyhow::Error::new(Categorized {
+                category,
+                error: err.into(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::{Categorize, ErrorCategory};
+
+    #[test]
+    fn test_error_category() {
+        let err = Err::<(), _>(anyhow!("No `social` section"))
+            .categorize(ErrorCategory::Config)
+            .context("Failed to toot")
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&err), Some(ErrorCategory::Config));
+        // the category doesn't show in the message
+        assert_eq!(format!("{err:#}"), "Failed to toot: No `social` section");
+
+        let err = std::fs::read_to_string("/no/such/file")
+            .context("Failed to read")
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&err), Some(ErrorCategory::Fs));
+        assert_eq!(ErrorCategory::of(&anyhow!("other")), None);
+    }
+}
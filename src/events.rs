@@ -0,0 +1,108 @@
+use std::{io::Write, path::Path, sync::Mutex};
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde_derive::Serialize;
+use tracing::error;
+
+// What `watch` is doing, written as newline-delimited JSON for other programs to follow
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    BuildStarted,
+    BuildSucceeded,
+    BuildFailed { error: String },
+    ScheduledChanged { next: Option<String>, count: usize },
+    PostPublished { path: String, dest: String },
+}
+
+#[derive(Debug, Serialize)]
+struct TimedEvent<'a> {
+    time: String,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug)]
+pub enum EventSink {
+    File(Mutex<std::fs::File>),
+    #[cfg(unix)]
+    Socket(Mutex<std::os::unix::net::UnixStream>),
+}
+
+impl EventSink {
+    // Append the events to `path`, created if needed
+    pub fn file(path: &Path) -> Result<Self> {
+        let file = std::fs::File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open events file `{}`", path.display()))?;
+        Ok(Self::File(Mutex::new(file)))
+    }
+
+    // Send the events to the unix socket listening at `path`
+    #[cfg(unix)]
+    pub fn socket(path: &Path) -> Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)
+            .with_context(|| format!("Failed to connect to events socket `{}`", path.display()))?;
+        Ok(Self::Socket(Mutex::new(stream)))
+    }
+
+    // Failing to report an event must not stop `watch`, so errors are only logged
+    pub fn emit(&self, event: &Event) {
+        if let Err(err) = self.write(event) {
+            error!("Failed to emit event {event:?}: {err:#}");
+        }
+    }
+
+    fn write(&self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_string(&TimedEvent {
+            time: Utc::now().to_rfc3339(),
+            event,
+        })?;
+        line.push('\n');
+        match self {
+            Self::File(file) => write_line(file, &line),
+            #[cfg(unix)]
+            Self::Socket(stream) => write_line(stream, &line),
+        }
+    }
+}
+
+fn write_line(out: &Mutex<impl Write>, line: &str) -> Result<()> {
+    match out.lock() {
+        Ok(mut out) => Ok(out.write_all(line.as_bytes())?),
+        Err(err) => bail!("Error getting lock on event sink: {err:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_dir;
+
+    use super::{Event, EventSink};
+
+    #[test]
+    fn test_events_file() {
+        let dir = test_dir();
+        let path = dir.path().join("events.jsonl");
+        let sink = EventSink::file(&path).unwrap();
+        sink.emit(&Event::BuildStarted);
+        sink.emit(&Event::PostPublished {
+            path: "post.md".to_string(),
+            dest: "content/posts/post.md".to_string(),
+        });
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "build_started");
+        assert!(events[0]["time"].is_string());
+        assert_eq!(events[1]["event"], "post_published");
+        assert_eq!(events[1]["dest"], "content/posts/post.md");
+    }
+}
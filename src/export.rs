@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::ValueEnum;
+use serde_derive::Serialize;
+
+use crate::{config::SiteConfig, format_date, watcher::SiteWatcher};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum DumpFormat {
+    Json,
+    Ical,
+}
+
+#[derive(Debug, Serialize)]
+struct ScheduledEntry {
+    slug: String,
+    date: String,
+}
+
+fn scheduled_entries(cfg: &SiteConfig) -> Result<Vec<(String, DateTime<Utc>)>> {
+    let watcher = SiteWatcher::new(cfg)?;
+    let scheduled = watcher
+        .scheduled
+        .into_inner()
+        .expect("Scheduled lock should not be poisoned");
+    let mut entries = Vec::new();
+    for (date, paths) in scheduled {
+        for path in paths {
+            entries.push((slug_of(&path)?, date));
+        }
+    }
+    Ok(entries)
+}
+
+fn slug_of(path: &Path) -> Result<String> {
+    let Some(stem) = path.file_stem() else {
+        bail!("Scheduled post `{}` has no file name", path.display());
+    };
+    Ok(stem.to_string_lossy().to_string())
+}
+
+fn to_json(entries: &[(String, DateTime<Utc>)], timezone: &FixedOffset) -> Result<String> {
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|(slug, date)| ScheduledEntry {
+            slug: slug.clone(),
+            date: format_date(&date.with_timezone(timezone)),
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&entries)?)
+}
+
+fn format_ical_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Length of the events: a publication is instant, but calendar apps hide or misplace the events
+// ending when they start
+const EVENT_DURATION: &str = "PT15M";
+
+fn to_ical(entries: &[(String, DateTime<Utc>)], now: &DateTime<Utc>) -> String {
+    // iCalendar mandates CRLF line endings
+    let mut ical =
+        String::from("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//emile//schedule//EN\r\n");
+    for (slug, date) in entries {
+        let date = format_ical_date(date);
+        ical.push_str("BEGIN:VEVENT\r\n");
+        ical.push_str(&format!("UID:{slug}-{date}@emile\r\n"));
+        ical.push_str(&format!("DTSTAMP:{}\r\n", format_ical_date(now)));
+        ical.push_str(&format!("DTSTART:{date}\r\n"));
+        ical.push_str(&format!("DURATION:{EVENT_DURATION}\r\n"));
+        ical.push_str(&format!("SUMMARY:Publish: {slug}\r\n"));
+        ical.push_str("END:VEVENT\r\n");
+    }
+    ical.push_str("END:VCALENDAR\r\n");
+    ical
+}
+
+pub fn dump_schedule(cfg: &SiteConfig, format: DumpFormat) -> Result<String> {
+    let entries = scheduled_entries(cfg)?;
+    match format {
+        DumpFormat::Json => to_json(&entries, &cfg.timezone),
+        DumpFormat::Ical => Ok(to_ical(&entries, &Utc::now())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::{DateTime, FixedOffset, Utc};
+
+    use super::{slug_of, to_ical, to_json};
+
+    fn entries() -> Vec<(String, DateTime<Utc>)> {
+        let date = "2025-06-27T10:00:00Z".parse().unwrap();
+        vec![("my-post".to_string(), date)]
+    }
+
+    #[test]
+    fn test_slug_of() {
+        assert_eq!(
+            slug_of(Path::new("content/scheduled/my-post.md")).unwrap(),
+            "my-post"
+        );
+        assert!(slug_of(Path::new("..")).is_err());
+    }
+
+    #[test]
+    fn test_to_json() {
+        let timezone = FixedOffset::east_opt(2 * 3600).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&entries(), &timezone).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{ "slug": "my-post", "date": "2025-06-27T12:00:00+02:00" }])
+        );
+    }
+
+    #[test]
+    fn test_to_ical() {
+        let now = "2025-06-20T08:30:00Z".parse().unwrap();
+        assert_eq!(
+            to_ical(&entries(), &now),
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//emile//schedule//EN\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:my-post-20250627T100000Z@emile\r\n\
+             DTSTAMP:20250620T083000Z\r\n\
+             DTSTART:20250627T100000Z\r\n\
+             DURATION:PT15M\r\n\
+             SUMMARY:Publish: my-post\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        );
+    }
+}
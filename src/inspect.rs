@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    config::SiteConfig,
+    format_date,
+    post::{dest_filename, extract_date, frontmatter, is_draft},
+    social::{post_lang_hashtags, post_title, taxonomy_terms},
+};
+
+// Fields of the post's frontmatter as emile reads them, by name. When emile's reading differs from
+// the TOML value, the latter is given too, to explain an unexpected behavior.
+fn inspect(post: &Path, content: &str, cfg: &SiteConfig) -> Result<Vec<(&'static str, String)>> {
+    let front = toml::from_str::<toml::Table>(&frontmatter(content)?)
+        .with_context(|| "Frontmatter is not valid TOML")?;
+    let front_str = |key: &str| front.get(key).and_then(|value| value.as_str());
+    let mut fields = Vec::new();
+
+    let title = post_title(content).unwrap_or_else(|err| format!("error: {err}"));
+    let title = match front_str("title") {
+        Some(toml_title) if toml_title != title => {
+            format!("{title} (the frontmatter says `{toml_title}`)")
+        }
+        None => format!("{title} (no `title` in the frontmatter)"),
+        _ => title,
+    };
+    fields.push(("title", title));
+
+    let date = match extract_date(post, cfg) {
+        Ok(date) => format_date(&date),
+        Err(err) => format!("error: {err:#}"),
+    };
+    fields.push(("date", date));
+    fields.push(("draft", is_draft(content).to_string()));
+
+    let taxonomy = cfg
+        .social
+        .as_ref()
+        .map_or("tags", |social| social.tag_taxonomy.as_str());
+    fields.push(("tags", taxonomy_terms(content, taxonomy).join(", ")));
+
+    let slug = front_str("slug").unwrap_or("<none>");
+    fields.push((
+        "slug",
+        format!(
+            "{slug} (published as `{}`)",
+            dest_filename(post, content).to_string_lossy()
+        ),
+    ));
+
+    if let Some(social) = &cfg.social {
+        let (lang, hashtags) = post_lang_hashtags(content, social)?;
+        fields.push(("lang", lang));
+        let hashtags: Vec<_> = hashtags.iter().map(|tag| format!("#{tag}")).collect();
+        fields.push(("social", hashtags.join(" ")));
+    }
+    Ok(fields)
+}
+
+pub fn print_frontmatter(post: &Path, cfg: &SiteConfig) -> Result<()> {
+    let content = std::fs::read_to_string(post)
+        .with_context(|| format!("Failed to read `{}`", post.to_string_lossy()))?;
+    for (name, value) in inspect(post, &content, cfg)? {
+        println!("{name:>6}: {value}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::inspect;
+
+    #[test]
+    fn test_inspect() {
+        let dir = test_dir();
+        let post = dir.path().join("inspect.md");
+        let content = "+++\ntitle = \"Say \\\"hi\\\"\"\ndate = 2024-06-03\nslug = \"hi\"\n\
+                       [taxonomies]\ntags = [\"rust\", \"zola\"]\n+++\nHi\n";
+        std::fs::write(&post, content).unwrap();
+
+        let fields = inspect(&post, content, &SiteConfig::default()).unwrap();
+        assert_eq!(
+            fields,
+            [
+                (
+                    "title",
+                    "Say \\hi\\ (the frontmatter says `Say \"hi\"`)".to_string()
+                ),
+                ("date", "2024-06-03T00:00:00+00:00".to_string()),
+                ("draft", "false".to_string()),
+                ("tags", "rust, zola".to_string()),
+                ("slug", "hi (published as `hi.md`)".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    borrow::Cow,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Error, Result};
+use chrono::{
+    offset::MappedLocalTime, DateTime, Datelike, Days, Duration, FixedOffset, Local, Months,
+    NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+};
+use regex::Regex;
+use tracing::warn;
+
+use crate::error::{Categorize, ErrorCategory};
+
+pub mod backfill;
+pub mod config;
+pub mod drafts;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod inspect;
+pub mod new;
+pub mod post;
+pub mod publish;
+pub mod repeat;
+pub mod reslug;
+pub mod scheduler;
+pub mod social;
+pub mod status;
+pub mod watcher;
+
+pub use config::{SiteConfig as Config, SiteConfigBuilder};
+pub use publish::{PublishOptions, PublishSource};
+pub use social::{http_client, push_to_social};
+
+// Create a draft post titled `title` in `drafts_creation_dir`, from the template of `kind` if
+// given. Returns the path of the draft.
+pub fn create_draft(cfg: &Config, title: &str, kind: Option<&str>) -> Result<PathBuf> {
+    new::create_draft(title, kind, None, None, cfg)
+}
+
+// Publish `post` to `publish_dest` and to social media if configured. The site is not rebuilt,
+// see `zola_build`. Returns the path of the published post.
+pub async fn publish(cfg: &Config, post: &Path) -> Result<String> {
+    publish::publish_post(post, cfg, &PublishOptions::default()).await
+}
+
+// Move `posts` (files or directories of posts) to `schedule_dir`, to be published at `date` by
+// the `watch` command. Unless `now_ok`, `date` must be at least `min_schedule_lead_secs` away.
+// Returns, for each post, what was done or why it couldn't be scheduled.
+pub fn schedule(
+    cfg: &Config,
+    date: &DateTime<FixedOffset>,
+    posts: &[PathBuf],
+    now_ok: bool,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    scheduler::schedule_posts(date, posts, cfg, now_ok)
+}
+
+// paths in `emile.toml` are relative to the root of the site, which holds Zola's `config.toml`
+pub fn ensure_zola_site() -> Result<()> {
+    if !std::path::Path::new("config.toml").is_file() {
+        return Err(anyhow::anyhow!(
+            "not a Zola site directory (no config.toml found)"
+        ))
+        .categorize(ErrorCategory::Config);
+    }
+    Ok(())
+}
+
+// Build the site with `build_command`, `zola build` by default
+pub fn zola_build(cfg: &Config) -> Result<()> {
+    let (program, args) = cfg
+        .build_command
+        .split_first()
+        .context("`build_command` is empty")?;
+    match std::process::Command::new(program).args(args).output() {
+        Ok(output) => {
+            if output.status.success() {
+                std::io::stdout().write_all(&output.stdout)?;
+                Ok(std::io::stdout().flush()?)
+            } else {
+                bail!(
+                    "{}\n{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+        }
+        Err(e) => match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                bail!("`{program}` was not found, please verify the PATH env.");
+            }
+            _ => {
+                bail!("{}", e);
+            }
+        },
+    }
+}
+
+fn parse_time_with_ref(
+    time_str: &str,
+    ref_date: DateTime<Local>,
+    default_time: &NaiveTime,
+) -> Result<DateTime<FixedOffset>, Error> {
+    let time_str = fix_time(time_str, &ref_date);
+    let datetime = match human_date_parser::from_human_time(&time_str)
+        .with_context(|| format!("Failure parsing `{time_str}`"))?
+    {
+        human_date_parser::ParseResult::DateTime(d) => d.fixed_offset(),
+        human_date_parser::ParseResult::Date(d) => {
+            let naive = d.and_time(*default_time);
+            resolve_local(naive.and_local_timezone(ref_date.timezone()), naive)?.fixed_offset()
+        }
+        human_date_parser::ParseResult::Time(t) => {
+            let now_time = ref_date.time();
+            let date = if t < now_time {
+                ref_date.checked_add_days(Days::new(1)).with_context(|| {
+                    format!(
+                        "Failed to add one day to `{}`",
+                        format_date(&ref_date.fixed_offset())
+                    )
+                })?
+            } else {
+                ref_date
+            };
+            resolve_local(date.with_time(t), date.date_naive().and_time(t))?.fixed_offset()
+        }
+    };
+
+    Ok(datetime)
+}
+
+// Local date of `naive`, warning when a DST change makes it happen twice, the later one being
+// taken, or never, in which case it is moved an hour later
+fn resolve_local(
+    mapped: MappedLocalTime<DateTime<Local>>,
+    naive: NaiveDateTime,
+) -> Result<DateTime<Local>> {
+    match mapped {
+        MappedLocalTime::Single(date) => Ok(date),
+        MappedLocalTime::Ambiguous(_, later) => {
+            warn!(
+                "`{naive}` happens twice because of a DST change, the later one is taken ({})",
+                format_date(&later.fixed_offset())
+            );
+            Ok(later)
+        }
+        MappedLocalTime::None => {
+            let shifted = Local
+                .from_local_datetime(&(naive + Duration::hours(1)))
+                .earliest()
+                .with_context(|| format!("`{naive}` doesn't exist in the local timezone"))?;
+            warn!(
+                "`{naive}` is skipped by a DST change, moved to {}",
+                format_date(&shifted.fixed_offset())
+            );
+            Ok(shifted)
+        }
+    }
+}
+
+pub fn parse_time(
+    time_str: &str,
+    default_time: &NaiveTime,
+) -> Result<DateTime<FixedOffset>, Error> {
+    let ref_date = Local::now();
+    parse_time_with_ref(time_str, ref_date, default_time)
+}
+
+// Times offered by `schedule` when none is given. `human_date_parser` tries "next <unit>" before
+// "next <weekday>", so "next monday" is read as "next m(inute)": "next week monday" is the Monday
+// of next week.
+pub const SCHEDULE_SUGGESTIONS: [&str; 3] = ["today 12:00", "tomorrow 09:00", "next week monday"];
+
+// The `SCHEDULE_SUGGESTIONS` still in the future of `now`, with the date they resolve to
+pub fn schedule_suggestions(
+    now: DateTime<Local>,
+    default_time: &NaiveTime,
+) -> Vec<(&'static str, DateTime<FixedOffset>)> {
+    SCHEDULE_SUGGESTIONS
+        .into_iter()
+        .filter_map(|time| {
+            let date = parse_time_with_ref(time, now, default_time).ok()?;
+            (date > now).then_some((time, date))
+        })
+        .collect()
+}
+
+// We accept omitted year and month. This function construct a minimal valid input to be parsed
+fn fix_time<'a>(s: &'a str, now: &DateTime<Local>) -> Cow<'a, str> {
+    let fix_day = |day, now: &DateTime<Local>| -> DateTime<Local> {
+        if day < now.day() {
+            let d = Local
+                .from_local_datetime(
+                    &NaiveDate::from_ymd_opt(now.year(), now.month(), day)
+                        .unwrap()
+                        .and_hms_opt(0, 0, 0)
+                        .unwrap(),
+                )
+                .unwrap();
+            d.checked_add_months(Months::new(1))
+                .unwrap_or_else(|| panic!("Add a month to `{}` blew up", now))
+        } else {
+            let diff = day - now.day();
+            now.checked_add_days(Days::new(diff as u64))
+                .unwrap_or_else(|| panic!("Add `{diff}` to `{now}` blew up"))
+        }
+    };
+
+    let day = Regex::new("^[0-3]?[0-9]$").expect("Failure compiling day regex");
+    if day.is_match(s) {
+        let day = s.parse::<u32>().unwrap();
+        let date = fix_day(day, now);
+        return Cow::Owned(format!("{}-{}-{}", date.year(), date.month(), date.day()));
+    }
+
+    let month_day = Regex::new(r"^(?<month>[0-1]?[0-9])\-(?<day>[0-3]?[0-9])$")
+        .expect("Failure compiling month regex");
+    if let Some(caps) = month_day.captures(s) {
+        let day = caps["day"]
+            .parse::<u32>()
+            .unwrap_or_else(|_| panic!("`{s}` is not a valid `month-day`"));
+        let month = caps["month"]
+            .parse::<u32>()
+            .unwrap_or_else(|_| panic!("`{s}` is not a valid `month-day`"));
+
+        let date = if month < now.month() {
+            let diff = now.month() - month;
+
+            now.checked_add_months(Months::new(12 - diff))
+                .unwrap_or_else(|| panic!("Adding a year to `{now}` blew up"))
+        } else {
+            let month_diff = month - now.month();
+            let d = now
+                .checked_add_months(Months::new(month_diff))
+                .unwrap_or_else(|| panic!("Adding `{month_diff}` to `{now}` blew up"));
+            fix_day(day, &d)
+        };
+
+        return Cow::Owned(format!("{}-{}-{}", date.year(), date.month(), date.day()));
+    }
+
+    Cow::Borrowed(s)
+}
+
+pub fn format_date(date: &DateTime<FixedOffset>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
+}
+
+pub fn format_utc_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+// `date` followed by the local time and how far it is from `now`, for the user to check a
+// scheduled date without converting it. Ex: "2024-06-27T12:00:00+00:00 (14:00 local, in 3 hours)"
+pub fn describe_date(date: &DateTime<FixedOffset>, now: DateTime<Utc>) -> String {
+    let local = date.with_timezone(&Local);
+    let local = if local.date_naive() == now.with_timezone(&Local).date_naive() {
+        local.format("%H:%M")
+    } else {
+        local.format("%Y-%m-%d %H:%M")
+    };
+    format!(
+        "{} ({local} local, {})",
+        format_date(date),
+        format_relative(date.to_utc(), now)
+    )
+}
+
+// "in 3 hours", "2 days ago"… rounded down to the largest unit
+fn format_relative(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let delta = date - now;
+    let secs = delta.num_seconds().abs();
+    let amount = match secs {
+        0..=59 => return "now".to_string(),
+        60..=3599 => (secs / 60, "minute"),
+        3600..=86_399 => (secs / 3600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    let amount = match amount {
+        (1, unit) => format!("1 {unit}"),
+        (nb, unit) => format!("{nb} {unit}s"),
+    };
+    if delta.num_seconds() > 0 {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+// A unique directory for a test, removed when dropped, even if the test panics
+#[cfg(test)]
+pub fn test_dir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("emile_test_")
+        .tempdir()
+        .expect("failed to create the test directory")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+    use crate::{format_relative, parse_time_with_ref, schedule_suggestions, SCHEDULE_SUGGESTIONS};
+
+    fn ref_date() -> (DateTime<Local>, NaiveTime) {
+        let def_time = NaiveTime::from_hms_opt(12, 00, 00).unwrap();
+        let now = Local
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(2024, 6, 27)
+                    .unwrap()
+                    .and_time(def_time),
+            )
+            .unwrap();
+        (now, def_time)
+    }
+
+    #[test]
+    fn test_month_in_the_past() {
+        let (now, def_time) = ref_date();
+        let r = parse_time_with_ref("05-27", now, &def_time).unwrap();
+        assert_eq!(r.year(), 2025);
+        assert_eq!(r.day(), 27);
+        assert_eq!(r.month(), 5);
+        let r = parse_time_with_ref("04-27", now, &def_time).unwrap();
+        assert_eq!(r.year(), 2025);
+        assert_eq!(r.day(), 27);
+        assert_eq!(r.month(), 4);
+    }
+
+    #[test]
+    fn test_month_in_the_future() {
+        let (now, def_time) = ref_date();
+        let r = parse_time_with_ref("07-27", now, &def_time).unwrap();
+        assert_eq!(r.year(), 2024);
+        assert_eq!(r.day(), 27);
+        assert_eq!(r.month(), 7);
+    }
+
+    #[test]
+    fn test_day_in_the_past() {
+        let (now, def_time) = ref_date();
+        let r = parse_time_with_ref("26", now, &def_time).unwrap();
+        dbg!(&r);
+        assert_eq!(r.year(), 2024);
+        assert_eq!(r.month(), 7);
+        assert_eq!(r.day(), 26)
+    }
+
+    #[test]
+    fn test_day_in_the_future() {
+        let (now, def_time) = ref_date();
+        let r = parse_time_with_ref("28", now, &def_time).unwrap();
+        dbg!(&r);
+        assert_eq!(r.year(), 2024);
+        assert_eq!(r.month(), 6);
+        assert_eq!(r.day(), 28)
+    }
+
+    #[test]
+    fn test_schedule_suggestions() {
+        let (now, def_time) = ref_date();
+        let suggestions = schedule_suggestions(now, &def_time);
+        // `human_date_parser` reads the dates from the clock, all after the reference
+        let times: Vec<_> = suggestions.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, SCHEDULE_SUGGESTIONS);
+        let (_, tomorrow) = suggestions[1];
+        assert_eq!(tomorrow.time(), NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        let (_, monday) = suggestions[2];
+        assert_eq!(monday.weekday(), Weekday::Mon);
+        assert_eq!(monday.time(), def_time);
+        assert!(monday.date_naive() > Local::now().date_naive());
+
+        // none is left once they are all past
+        let later = now.with_year(2100).unwrap();
+        assert!(schedule_suggestions(later, &def_time).is_empty());
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = "2024-06-27T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let relative = |date: &str| format_relative(date.parse().unwrap(), now);
+        assert_eq!(relative("2024-06-27T12:00:30Z"), "now");
+        assert_eq!(relative("2024-06-27T12:01:30Z"), "in 1 minute");
+        assert_eq!(relative("2024-06-27T15:10:00Z"), "in 3 hours");
+        assert_eq!(relative("2024-06-30T11:00:00Z"), "in 2 days");
+        assert_eq!(relative("2024-06-27T10:00:00Z"), "2 hours ago");
+    }
+}
@@ -1,82 +1,275 @@
-use std::{borrow::Cow, io::Write, sync::Arc};
-
-use anyhow::{bail, Context, Error, Result};
-use chrono::{
-    DateTime, Datelike, Days, FixedOffset, Local, Months, NaiveDate, NaiveTime, TimeZone, Timelike,
-    Utc,
+use std::{
+    io::{BufRead, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::Arc,
 };
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local};
 use clap::Parser;
-use config::SiteConfigBuilder;
+use emile::{
+    backfill::{self, Backfilled},
+    config::expand_cli_path,
+    drafts, ensure_zola_site,
+    error::{Categorize, ErrorCategory},
+    events::EventSink,
+    export, format_date, inspect, new, parse_time, publish, reslug, schedule_suggestions,
+    scheduler, social, status, watcher,
+    watcher::SiteWatcher,
+    zola_build, Config, PublishOptions, PublishSource, SiteConfigBuilder,
+};
 
-mod config;
-mod new;
 mod opt;
-mod post;
-mod publish;
-mod scheduler;
-mod social;
-mod watcher;
 
-use opt::{Commands, Opt};
-use regex::Regex;
+use opt::{Commands, LogRotation, Opt};
 use tracing::{error, info};
+use tracing_appender::rolling::RollingFileAppender;
 use tracing_subscriber::{fmt::time::UtcTime, prelude::*, EnvFilter};
-use watcher::SiteWatcher;
 
+// Print a success message on stdout, or only log it at debug level with `--quiet`
+macro_rules! success {
+    ($quiet:expr, $($arg:tt)*) => {
+        if $quiet {
+            tracing::debug!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[cfg(unix)]
+fn events_socket_sink(path: &Path) -> Result<EventSink> {
+    EventSink::socket(path)
+}
+
+#[cfg(not(unix))]
+fn events_socket_sink(_path: &Path) -> Result<EventSink> {
+    bail!("`--events-socket` is only available on unix")
+}
+
+fn file_appender(
+    log_dir: &Path,
+    rotation: LogRotation,
+    file_name: &str,
+) -> Result<RollingFileAppender> {
+    if !log_dir.is_dir() {
+        error!("{} is not a valid directory", log_dir.to_string_lossy());
+        bail!("Invalid log dir");
+    }
+    Ok(match rotation {
+        LogRotation::Never => tracing_appender::rolling::never(log_dir, file_name),
+        LogRotation::Daily => tracing_appender::rolling::daily(log_dir, file_name),
+        LogRotation::Hourly => tracing_appender::rolling::hourly(log_dir, file_name),
+    })
+}
+
+fn no_social_section() -> Result<()> {
+    Err(anyhow!("No `social` section in `emile.toml`")).categorize(ErrorCategory::Config)
+}
+
+// Errors are printed as when returned from `main`, with an exit code depending on their category
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            ExitCode::from(ErrorCategory::of(&err).map_or(1, ErrorCategory::exit_code))
+        }
+    }
+}
+
+async fn run() -> Result<()> {
     let opt = Opt::parse();
     // log setup
-    let _guard = if let Some(log_dir) = opt.log_dir {
-        if !log_dir.is_dir() {
-            error!("{} is not a valid directory", log_dir.to_string_lossy());
-            bail!("Invalid log dir");
-        }
-        let file_appender = tracing_appender::rolling::never(log_dir, "emile.log");
-        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
-        tracing_subscriber::registry()
-            .with(
-                tracing_subscriber::fmt::layer()
-                    .compact()
-                    .with_timer(UtcTime::rfc_3339())
-                    .with_writer(non_blocking)
-                    .with_target(false),
-            )
-            .with(EnvFilter::try_from_env("EMILE_LOG").or_else(|_| EnvFilter::try_new("info"))?)
-            .init();
-        Some(guard)
+    let mut _guards = Vec::new();
+    let file_layer = if let Some(log_dir) = &opt.log_dir {
+        let log_dir = expand_cli_path(log_dir).context("Invalid `--log-dir`")?;
+        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender(
+            &log_dir,
+            opt.log_rotation,
+            &opt.log_file,
+        )?);
+        _guards.push(guard);
+        Some(
+            tracing_subscriber::fmt::layer()
+                .compact()
+                .with_timer(UtcTime::rfc_3339())
+                .with_writer(non_blocking)
+                .with_target(false),
+        )
     } else {
-        tracing_subscriber::registry()
-            .with(
-                tracing_subscriber::fmt::layer()
-                    .compact()
-                    .with_timer(UtcTime::rfc_3339())
-                    .with_target(false),
-            )
-            .with(EnvFilter::try_from_env("EMILE_LOG").or_else(|_| EnvFilter::try_new("info"))?)
-            .init();
         None
     };
+    let stdout_layer = file_layer.is_none().then(|| {
+        tracing_subscriber::fmt::layer()
+            .compact()
+            .with_timer(UtcTime::rfc_3339())
+            .with_target(false)
+    });
+    let json_layer = if let Some(json_log_dir) = &opt.json_log_dir {
+        let json_log_dir = expand_cli_path(json_log_dir).context("Invalid `--json-log-dir`")?;
+        let (non_blocking, guard) = tracing_appender::non_blocking(file_appender(
+            &json_log_dir,
+            opt.log_rotation,
+            &opt.json_log_file,
+        )?);
+        _guards.push(guard);
+        Some(
+            tracing_subscriber::fmt::layer()
+                .json()
+                .with_timer(UtcTime::rfc_3339())
+                .with_writer(non_blocking),
+        )
+    } else {
+        None
+    };
+    tracing_subscriber::registry()
+        .with(file_layer)
+        .with(stdout_layer)
+        .with(json_layer)
+        .with(EnvFilter::try_from_env("EMILE_LOG").or_else(|_| EnvFilter::try_new("info"))?)
+        .init();
 
     info!("emile {}", clap::crate_version!());
 
+    let quiet = opt.quiet;
+    let template_dir = opt.template_dir;
+    let offline = opt.no_network
+        || std::env::var("EMILE_OFFLINE")
+            .map(|var| !matches!(var.as_str(), "" | "0" | "false"))
+            .unwrap_or(false);
+    if offline {
+        info!("Offline: nothing will be sent to the social servers");
+    }
+    let get_config = || {
+        let mut cfg = SiteConfigBuilder::get_config();
+        if let Some(dir) = &template_dir {
+            cfg.override_templates_dir(dir.clone());
+        }
+        if offline {
+            cfg.set_offline();
+        }
+        cfg
+    };
+
     match opt.command {
-        Commands::New { title } => {
-            let cfg = SiteConfigBuilder::get_config();
-            new::create_draft(&title, &cfg)
+        Commands::New {
+            title,
+            kind,
+            stdin,
+            from,
+        } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            let body = if stdin {
+                let mut body = String::new();
+                std::io::stdin().read_to_string(&mut body)?;
+                Some(body)
+            } else {
+                None
+            };
+            let dest = match (from, title) {
+                (Some(url), title) => {
+                    new::create_link_draft(
+                        &url,
+                        title.as_deref(),
+                        kind.as_deref(),
+                        body.as_deref(),
+                        &cfg,
+                    )
+                    .await?
+                }
+                (None, Some(title)) => {
+                    new::create_draft(&title, kind.as_deref(), body.as_deref(), None, &cfg)?
+                }
+                (None, None) => unreachable!("clap requires a title without `--from`"),
+            };
+            success!(quiet, "Success: post `{}` created.", dest.to_string_lossy());
+            Ok(())
         }
-        Commands::Publish { post } => {
-            let cfg = SiteConfigBuilder::get_config();
-            let dest = publish::publish_post(&post, &cfg).await?;
-            zola_build()?;
-            println!("Success: post `{dest}` published.");
+        Commands::Publish {
+            post,
+            dest,
+            base_url,
+            lang,
+            template,
+            keep_date,
+            validate_build,
+            open,
+            yes,
+        } => {
+            ensure_zola_site()?;
+            let mut cfg = get_config();
+            if let Some(dest) = dest {
+                cfg.override_publish_dest(dest);
+            }
+            if let Some(base_url) = base_url {
+                cfg.override_base_url(&base_url)?;
+            }
+            let opts = PublishOptions {
+                lang,
+                template,
+                keep_date,
+                validate_build,
+                source: PublishSource::Manual,
+            };
+            if post.is_dir() {
+                if open {
+                    bail!("`--open` needs a single post, not a directory");
+                }
+                if !yes
+                    && !confirm(&format!(
+                        "Publish the posts of `{}` to `{}`?",
+                        post.to_string_lossy(),
+                        cfg.publish_dest.to_string_lossy()
+                    ))?
+                {
+                    println!("Aborted.");
+                    return Ok(());
+                }
+                return publish_dir(&post, &cfg, &opts, quiet).await;
+            }
+            if !yes
+                && !confirm(&format!(
+                    "Publish `{}` to `{}`?",
+                    post.to_string_lossy(),
+                    cfg.publish_dest.to_string_lossy()
+                ))?
+            {
+                println!("Aborted.");
+                return Ok(());
+            }
+            let dest = publish::publish_post(&post, &cfg, &opts).await?;
+            zola_build(&cfg)?;
+            success!(quiet, "Success: post `{dest}` published.");
+            if open {
+                open_post(&cfg, Path::new(&dest));
+            }
             Ok(())
         }
-        Commands::Watch { website } => {
+        Commands::Watch {
+            website,
+            dry_run,
+            events_file,
+            events_socket,
+        } => {
+            // opened before moving to `website`, so relative paths are from where emile is run
+            let events = match (events_file, events_socket) {
+                (Some(path), _) => Some(EventSink::file(&path)?),
+                (_, Some(path)) => Some(events_socket_sink(&path)?),
+                _ => None,
+            };
             std::env::set_current_dir(website)?;
-            let cfg = Arc::new(SiteConfigBuilder::get_config());
+            ensure_zola_site()?;
+            let cfg = Arc::new(get_config());
             tracing::debug!("{:?}", cfg);
-            let change_watcher = Arc::new(SiteWatcher::new(&cfg)?);
+            scheduler::warn_visible_scheduled(&cfg);
+            let mut site_watcher = SiteWatcher::new(&cfg)?;
+            site_watcher.dry_run = dry_run;
+            site_watcher.events = events;
+            let change_watcher = Arc::new(site_watcher);
             let schedule_watcher = change_watcher.clone();
             let (tx_scheduler, rx_scheduler) = tokio::sync::mpsc::unbounded_channel();
 
@@ -95,210 +288,411 @@ async fn main() -> Result<()> {
             watcher::start_watching(change_watcher, cfg, tx_scheduler).await?;
             Ok(())
         }
-        Commands::Schedule { time, post } => {
-            let cfg = SiteConfigBuilder::get_config();
-            let date = parse_time(&time, &cfg.default_sch_time)?;
-            scheduler::schedule_post(&date, &post, &cfg)
+        Commands::Schedule {
+            time,
+            mut posts,
+            now_ok,
+            after,
+            repeat,
+            yes,
+        } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            // without a time, the first post is taken for it
+            let time = match time {
+                Some(time) if Path::new(&time).exists() => {
+                    posts.insert(0, time.into());
+                    None
+                }
+                time => time,
+            };
+            if posts.is_empty() {
+                bail!("No post to schedule");
+            }
+            let list = posts
+                .iter()
+                .map(|post| format!("`{}`", post.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let res = match (after, time) {
+                (Some(after), Some(time)) => {
+                    if !yes && !confirm(&format!("Schedule {list} {time} after `{after}`?"))? {
+                        println!("Aborted.");
+                        return Ok(());
+                    }
+                    scheduler::schedule_posts_after(&after, &time, &posts, &cfg, now_ok)?
+                }
+                (Some(after), None) => {
+                    bail!("Missing the delay after the publication of `{after}`, like \"+2 days\"")
+                }
+                (None, time) => {
+                    let date = match time {
+                        Some(time) => {
+                            let date = parse_time(&time, &cfg.default_sch_time)?;
+                            let every = repeat
+                                .as_ref()
+                                .map(|every| format!(", then every {every}"))
+                                .unwrap_or_default();
+                            let question =
+                                format!("Schedule {list} at {}{every}?", format_date(&date));
+                            if !yes && !confirm(&question)? {
+                                println!("Aborted.");
+                                return Ok(());
+                            }
+                            date
+                        }
+                        // picking the time is the confirmation
+                        None => match pick_time(&list, &cfg)? {
+                            Some(date) => date,
+                            None => {
+                                println!("Aborted.");
+                                return Ok(());
+                            }
+                        },
+                    };
+                    match &repeat {
+                        Some(every) => {
+                            scheduler::schedule_posts_repeat(&date, every, &posts, &cfg, now_ok)?
+                        }
+                        None => scheduler::schedule_posts(&date, &posts, &cfg, now_ok)?,
+                    }
+                }
+            };
+            let res = report_scheduled(&res, quiet);
+            scheduler::warn_visible_scheduled(&cfg);
+            res
         }
-    }
-}
-
-fn zola_build() -> Result<()> {
-    match std::process::Command::new("zola").arg("build").output() {
-        Ok(output) => {
-            if output.status.success() {
-                std::io::stdout().write_all(&output.stdout)?;
-                Ok(std::io::stdout().flush()?)
-            } else {
-                bail!(
-                    "{}\n{}",
-                    String::from_utf8_lossy(&output.stdout),
-                    String::from_utf8_lossy(&output.stderr)
-                );
+        Commands::Queue { post } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            let res =
+                scheduler::queue_post(&post, &cfg).map(|scheduled| success!(quiet, "{scheduled}"));
+            scheduler::warn_visible_scheduled(&cfg);
+            res
+        }
+        Commands::Retract { post, yes } => {
+            let cfg = get_config();
+            let Some(social_cfg) = cfg.social.as_ref() else {
+                return no_social_section();
+            };
+            if !yes
+                && !confirm(&format!(
+                    "Delete the social posts of `{}`? This can't be undone",
+                    post.to_string_lossy()
+                ))?
+            {
+                println!("Aborted.");
+                return Ok(());
             }
+            let client = social::http_client(social_cfg).categorize(ErrorCategory::Config)?;
+            let content = std::fs::read_to_string(&post)?;
+            let (new_content, nb_failed) =
+                social::retract_from_social(&client, social_cfg, &content)
+                    .await
+                    .categorize(ErrorCategory::Social)?;
+            std::fs::write(&post, new_content)?;
+            if nb_failed > 0 {
+                return Err(anyhow!("{nb_failed} social post(s) could not be deleted"))
+                    .categorize(ErrorCategory::Social);
+            }
+            success!(
+                quiet,
+                "Success: social posts of `{}` deleted.",
+                post.to_string_lossy()
+            );
+            Ok(())
         }
-        Err(e) => match e.kind() {
-            std::io::ErrorKind::NotFound => {
-                bail!("`zola` was not found, please verify the PATH env.");
+        Commands::Toot { text, lang } => {
+            let cfg = get_config();
+            let Some(social_cfg) = cfg.social.as_ref() else {
+                return no_social_section();
+            };
+            let client = social::http_client(social_cfg).categorize(ErrorCategory::Config)?;
+            let (posted, nb_failed) =
+                social::post_status(&client, social_cfg, &text, lang.as_deref())
+                    .await
+                    .categorize(ErrorCategory::Social)?;
+            for (instance, url) in posted {
+                println!("Posted on {} `{}`: {url}", instance.api, instance.server);
             }
-            _ => {
-                bail!("{}", e);
+            if nb_failed > 0 {
+                return Err(anyhow!(
+                    "{nb_failed} social instance(s) could not be posted on"
+                ))
+                .categorize(ErrorCategory::Social);
             }
-        },
-    }
-}
-
-fn parse_time_with_ref(
-    time_str: &str,
-    ref_date: DateTime<Local>,
-    default_time: &NaiveTime,
-) -> Result<DateTime<FixedOffset>, Error> {
-    let time_str = fix_time(time_str, &ref_date);
-    let datetime = match human_date_parser::from_human_time(&time_str)
-        .with_context(|| format!("Failure parsing `{time_str}`"))?
-    {
-        human_date_parser::ParseResult::DateTime(d) => d.fixed_offset(),
-        human_date_parser::ParseResult::Date(d) => {
-            let datetime: DateTime<FixedOffset> = d
-                .and_hms_opt(
-                    default_time.hour(),
-                    default_time.minute(),
-                    default_time.second(),
-                )
-                .unwrap()
-                .and_local_timezone(ref_date.timezone())
-                .unwrap()
-                .into();
-            datetime
+            Ok(())
         }
-        human_date_parser::ParseResult::Time(t) => {
-            let now_time = ref_date.time();
-            let date = if t < now_time {
-                ref_date.checked_add_days(Days::new(1)).with_context(|| {
-                    format!(
-                        "Failed to add one day to `{}`",
-                        format_date(&ref_date.fixed_offset())
-                    )
-                })?
-            } else {
-                ref_date
+        Commands::CheckTokens => {
+            let cfg = get_config();
+            let Some(social_cfg) = cfg.social.as_ref() else {
+                return no_social_section();
             };
-            match date.with_time(t) {
-                chrono::offset::MappedLocalTime::Single(dt) => dt.fixed_offset(),
-                chrono::offset::MappedLocalTime::Ambiguous(_, dt) => dt.fixed_offset(),
-                chrono::offset::MappedLocalTime::None => bail!("Parsing time blew up"),
+            let client = social::http_client(social_cfg).categorize(ErrorCategory::Config)?;
+            let checked = social::check_tokens(&client, social_cfg)
+                .await
+                .categorize(ErrorCategory::Social)?;
+            let mut failed = 0;
+            for (instance, account) in checked {
+                match account {
+                    Ok(account) => println!(
+                        "{} `{}`: ok, logged in as `{account}`",
+                        instance.api, instance.server
+                    ),
+                    Err(err) => {
+                        failed += 1;
+                        println!("{} `{}`: {err:#}", instance.api, instance.server);
+                    }
+                }
             }
+            if failed > 0 {
+                return Err(anyhow!(
+                    "{failed} social instance(s) could not be logged in"
+                ))
+                .categorize(ErrorCategory::Social);
+            }
+            Ok(())
         }
-    };
-
-    Ok(datetime)
+        Commands::Backfill { api, count } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            let results = backfill::backfill(count, api, &cfg).await?;
+            let mut nb_failed = 0;
+            for (post, res) in &results {
+                match res {
+                    Ok(Backfilled::Posted) => {
+                        success!(quiet, "Posted `{}` on {api}", post.to_string_lossy())
+                    }
+                    Ok(Backfilled::AlreadyPosted) => {
+                        success!(quiet, "`{}` is already on {api}", post.to_string_lossy())
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "Error: failed to post `{}`: {err:#}",
+                            post.to_string_lossy()
+                        );
+                        nb_failed += 1;
+                    }
+                }
+            }
+            if nb_failed > 0 {
+                return Err(anyhow!("{nb_failed} post(s) could not be posted on {api}"))
+                    .categorize(ErrorCategory::Social);
+            }
+            Ok(())
+        }
+        Commands::Status => status::print_status(),
+        Commands::Cancel { slug, yes } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            if !yes
+                && !confirm(&format!(
+                    "Cancel the publication of `{slug}` and move it back to `{}`?",
+                    cfg.drafts_creation_dir.to_string_lossy()
+                ))?
+            {
+                println!("Aborted.");
+                return Ok(());
+            }
+            let dest = scheduler::cancel_scheduled(&slug, &cfg)?;
+            success!(
+                quiet,
+                "Cancelled `{slug}`, moved back to `{}`",
+                dest.to_string_lossy()
+            );
+            Ok(())
+        }
+        Commands::Drafts => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            drafts::list_drafts(&cfg)
+        }
+        Commands::TemplateCheck => {
+            let cfg = get_config();
+            let Some(social_cfg) = cfg.social.as_ref() else {
+                return no_social_section();
+            };
+            let checked = social::check_templates(social_cfg).categorize(ErrorCategory::Config)?;
+            let mut failed = 0;
+            for (template, unknown) in checked {
+                if unknown.is_empty() {
+                    println!("`{}`: ok", template.to_string_lossy());
+                } else {
+                    failed += 1;
+                    let unknown: Vec<_> =
+                        unknown.iter().map(|name| format!("{{{name}}}")).collect();
+                    println!(
+                        "`{}`: unknown placeholder(s) {}",
+                        template.to_string_lossy(),
+                        unknown.join(", ")
+                    );
+                }
+            }
+            if failed > 0 {
+                return Err(anyhow!(
+                    "{failed} template(s) with unknown placeholders, known ones are {{title}}, \
+                     {{description}}, {{link}} and {{tags}}, or {{links}} in `link_template`"
+                ))
+                .categorize(ErrorCategory::Config);
+            }
+            Ok(())
+        }
+        Commands::Frontmatter { post } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            inspect::print_frontmatter(&post, &cfg)
+        }
+        Commands::Reslug {
+            posts,
+            fix_links,
+            keep_urls,
+            since,
+            dry_run,
+        } => {
+            ensure_zola_site()?;
+            let cfg = get_config();
+            let since = since.as_deref().map(scheduler::parse_delay).transpose()?;
+            reslug::reslug_posts(&posts, fix_links, keep_urls, since, dry_run, &cfg)
+        }
+        Commands::DumpSchedule { format, output } => {
+            let cfg = get_config();
+            let dump = export::dump_schedule(&cfg, format)?;
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, dump)?;
+                    success!(
+                        quiet,
+                        "Success: schedule exported to `{}`.",
+                        output.to_string_lossy()
+                    );
+                }
+                None => println!("{dump}"),
+            }
+            Ok(())
+        }
+    }
 }
 
-fn parse_time(time_str: &str, default_time: &NaiveTime) -> Result<DateTime<FixedOffset>, Error> {
-    let ref_date = Local::now();
-    parse_time_with_ref(time_str, ref_date, default_time)
+// Open the URL of the published post in the browser, only in an interactive session and when the
+// website's URL is known: loading the `social` section already checked its `base_url`
+fn open_post(cfg: &Config, dest: &Path) {
+    if !std::io::stdout().is_terminal() {
+        return;
+    }
+    let Some(social_cfg) = cfg.social.as_ref() else {
+        println!("Not opening the post: its URL needs the `social` section of `emile.toml`.");
+        return;
+    };
+    let url = social::post_link(social_cfg, dest);
+    if let Err(err) = open::that(&url) {
+        error!("Failed to open `{url}`: {err}");
+    }
 }
 
-// We accept omitted year and month. This function construct a minimal valid input to be parsed
-fn fix_time<'a>(s: &'a str, now: &DateTime<Local>) -> Cow<'a, str> {
-    let fix_day = |day, now: &DateTime<Local>| -> DateTime<Local> {
-        if day < now.day() {
-            let d = Local
-                .from_local_datetime(
-                    &NaiveDate::from_ymd_opt(now.year(), now.month(), day)
-                        .unwrap()
-                        .and_hms_opt(0, 0, 0)
-                        .unwrap(),
-                )
-                .unwrap();
-            d.checked_add_months(Months::new(1))
-                .expect(&format!("Add a month to `{}` blew up", now))
-        } else {
-            let diff = day - now.day();
-            now.checked_add_days(Days::new(diff as u64))
-                .expect(&format!("Add `{diff}` to `{now}` blew up"))
+// Publish every post of `dir`, then build the site once if any was published
+async fn publish_dir(dir: &Path, cfg: &Config, opts: &PublishOptions, quiet: bool) -> Result<()> {
+    let results = publish::publish_dir(dir, cfg, opts).await?;
+    if results.is_empty() {
+        bail!("No post to publish in `{}`", dir.to_string_lossy());
+    }
+    let mut nb_failed = 0;
+    for (post, res) in &results {
+        match res {
+            Ok(dest) => success!(quiet, "Published `{}` to `{dest}`", post.to_string_lossy()),
+            Err(err) => {
+                eprintln!(
+                    "Error: failed to publish `{}`: {err:#}",
+                    post.to_string_lossy()
+                );
+                nb_failed += 1;
+            }
         }
-    };
-
-    let day = Regex::new("^[0-3]?[0-9]$").expect("Failure compiling day regex");
-    if day.is_match(s) {
-        let day = u32::from_str_radix(s, 10).unwrap();
-        let date = fix_day(day, now);
-        return Cow::Owned(format!("{}-{}-{}", date.year(), date.month(), date.day()));
     }
-
-    let month_day = Regex::new(r"^(?<month>[0-1]?[0-9])\-(?<day>[0-3]?[0-9])$")
-        .expect("Failure compiling month regex");
-    if let Some(caps) = month_day.captures(s) {
-        let day = u32::from_str_radix(&caps["day"], 10)
-            .expect(&format!("`{s}` is not a valid `month-day`"));
-        let month = u32::from_str_radix(&caps["month"], 10)
-            .expect(&format!("`{s}` is not a valid `month-day`"));
-
-        let date = if month < now.month() {
-            let diff = now.month() - month;
-
-            now.checked_add_months(Months::new(12 - diff))
-                .expect(&format!("Adding a year to `{now}` blew up"))
-        } else {
-            let month_diff = month - now.month();
-            let d = now
-                .checked_add_months(Months::new(month_diff))
-                .expect(&format!("Adding `{month_diff}` to `{now}` blew up"));
-            fix_day(day, &d)
-        };
-
-        return Cow::Owned(format!("{}-{}-{}", date.year(), date.month(), date.day()));
+    // a post failing on social media is published anyway
+    if results
+        .iter()
+        .any(|(post, res)| res.is_ok() || !post.exists())
+    {
+        zola_build(cfg)?;
     }
-
-    Cow::Borrowed(s)
-}
-
-fn format_date(date: &DateTime<FixedOffset>) -> String {
-    date.format("%Y-%m-%dT%H:%M:%S%:z").to_string()
-}
-
-fn format_utc_date(date: &DateTime<Utc>) -> String {
-    date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    if nb_failed > 0 {
+        bail!(
+            "{nb_failed} of {} post(s) could not be published",
+            results.len()
+        );
+    }
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone};
-
-    use crate::parse_time_with_ref;
-
-    fn ref_date() -> (DateTime<Local>, NaiveTime) {
-        let def_time = NaiveTime::from_hms_opt(12, 00, 00).unwrap();
-        let now = Local
-            .from_local_datetime(
-                &NaiveDate::from_ymd_opt(2024, 06, 27)
-                    .unwrap()
-                    .and_time(def_time),
-            )
-            .unwrap();
-        (now, def_time)
+// Print what was done for each scheduled post, failing if any couldn't be
+fn report_scheduled(results: &[(PathBuf, Result<String>)], quiet: bool) -> Result<()> {
+    let mut nb_failed = 0;
+    for (post, res) in results {
+        match res {
+            Ok(scheduled) => success!(quiet, "{scheduled}"),
+            Err(err) => {
+                eprintln!(
+                    "Error: failed to schedule `{}`: {err:#}",
+                    post.to_string_lossy()
+                );
+                nb_failed += 1;
+            }
+        }
     }
-
-    #[test]
-    fn test_month_in_the_past() {
-        let (now, def_time) = ref_date();
-        let r = parse_time_with_ref("05-27", now.clone(), &def_time).unwrap();
-        assert_eq!(r.year(), 2025);
-        assert_eq!(r.day(), 27);
-        assert_eq!(r.month(), 5);
-        let r = parse_time_with_ref("04-27", now.clone(), &def_time).unwrap();
-        assert_eq!(r.year(), 2025);
-        assert_eq!(r.day(), 27);
-        assert_eq!(r.month(), 4);
+    if nb_failed > 0 {
+        bail!(
+            "{nb_failed} of {} post(s) could not be scheduled",
+            results.len()
+        );
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_month_in_the_future() {
-        let (now, def_time) = ref_date();
-        let r = parse_time_with_ref("07-27", now.clone(), &def_time).unwrap();
-        assert_eq!(r.year(), 2024);
-        assert_eq!(r.day(), 27);
-        assert_eq!(r.month(), 7);
+// Ask when to schedule `list`, among `SCHEDULE_SUGGESTIONS` or any time `parse_time` accepts.
+// `None` if the user gave up.
+fn pick_time(list: &str, cfg: &Config) -> Result<Option<DateTime<FixedOffset>>> {
+    if !std::io::stdin().is_terminal() {
+        bail!("Missing the time to schedule {list} at, required when not in a terminal");
     }
-
-    #[test]
-    fn test_day_in_the_past() {
-        let (now, def_time) = ref_date();
-        let r = parse_time_with_ref("26", now.clone(), &def_time).unwrap();
-        dbg!(&r);
-        assert_eq!(r.year(), 2024);
-        assert_eq!(r.month(), 7);
-        assert_eq!(r.day(), 26)
+    let suggestions = schedule_suggestions(Local::now(), &cfg.default_sch_time);
+    println!("When to schedule {list}?");
+    for (i, (time, date)) in suggestions.iter().enumerate() {
+        println!("  {}) {time} ({})", i + 1, format_date(date));
     }
+    loop {
+        print!("Number or time (empty to abort): ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        let picked = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|nb| suggestions.get(nb.checked_sub(1)?));
+        if let Some((_, date)) = picked {
+            return Ok(Some(*date));
+        }
+        match parse_time(answer, &cfg.default_sch_time) {
+            Ok(date) => return Ok(Some(date)),
+            Err(err) => println!("{err:#}"),
+        }
+    }
+}
 
-    #[test]
-    fn test_day_in_the_future() {
-        let (now, def_time) = ref_date();
-        let r = parse_time_with_ref("28", now.clone(), &def_time).unwrap();
-        dbg!(&r);
-        assert_eq!(r.year(), 2024);
-        assert_eq!(r.month(), 6);
-        assert_eq!(r.day(), 28)
+// Ask `question` on the terminal, anything but "y" or "yes" is a no. Always yes when stdout is not a
+// terminal, to not block scripts.
+fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdout().is_terminal() {
+        return Ok(true);
     }
+    print!("{question} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
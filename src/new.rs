@@ -1,33 +1,164 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use anyhow::{bail, Result};
-use chrono::{DateTime, Datelike, Local, NaiveDate, Timelike};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
 use slug::slugify;
+use tracing::warn;
 
 use crate::config::SiteConfig;
-use crate::format_date;
-use crate::post::modify_front;
+use crate::post::{is_delimiter, modify_front};
+use crate::publish::does_same_title_exist;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+// What a draft linking to a web page is pre-filled with
+#[derive(Debug, Default, PartialEq)]
+pub struct LinkInfo {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// `<title>` of the page, and its OpenGraph description, or the plain one
+fn parse_link_info(html: &str) -> LinkInfo {
+    lazy_static! {
+        static ref TITLE: Regex = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+        static ref META: Regex = Regex::new(r"(?is)<meta\s[^>]*>").unwrap();
+        static ref ATTR: Regex =
+            Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    }
+    let title = TITLE
+        .captures(html)
+        .map(|caps| {
+            decode_entities(
+                caps[1]
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .as_str(),
+            )
+        })
+        .filter(|title| !title.is_empty());
+
+    let mut og_description = None;
+    let mut description = None;
+    for meta in META.find_iter(html) {
+        let attrs: HashMap<String, &str> = ATTR
+            .captures_iter(meta.as_str())
+            .map(|caps| {
+                let value = caps.get(2).or(caps.get(3)).map(|v| v.as_str());
+                (caps[1].to_lowercase(), value.unwrap_or_default())
+            })
+            .collect();
+        let Some(content) = attrs.get("content") else {
+            continue;
+        };
+        match attrs.get("property").or(attrs.get("name")).copied() {
+            Some("og:description") => og_description = Some(decode_entities(content)),
+            Some("description") => description = Some(decode_entities(content)),
+            _ => {}
+        }
+    }
+
+    LinkInfo {
+        title,
+        description: og_description.or(description),
+    }
+}
+
+pub async fn fetch_link_info(url: &str) -> Result<LinkInfo> {
+    let client = reqwest::Client::builder().timeout(FETCH_TIMEOUT).build()?;
+    let html = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    Ok(parse_link_info(&html))
+}
+
+// Create a draft linking to `url`, with the title, unless given, and description of the page. If
+// the page can't be fetched, the draft is created anyway, titled after `url` if no title is given.
+// Returns the path of the draft.
+pub async fn create_link_draft(
+    url: &str,
+    title: Option<&str>,
+    kind: Option<&str>,
+    body: Option<&str>,
+    cfg: &SiteConfig,
+) -> Result<PathBuf> {
+    let info = fetch_link_info(url).await.unwrap_or_else(|err| {
+        warn!("Failed to fetch `{url}`, the draft is not pre-filled: {err}");
+        LinkInfo::default()
+    });
+    let title = title.or(info.title.as_deref()).unwrap_or(url);
+    let link = format!("[{title}]({url})\n");
+    let body = match body {
+        Some(body) => format!("{link}\n{body}"),
+        None => link,
+    };
+    create_draft(title, kind, Some(&body), info.description.as_deref(), cfg)
+}
+
+fn draft_template<'a>(kind: Option<&str>, cfg: &'a SiteConfig) -> Result<&'a str> {
+    match kind {
+        Some(kind) => match cfg.draft_templates.get(kind) {
+            Some(template) => Ok(template),
+            None => {
+                let mut known: Vec<&str> = cfg.draft_templates.keys().map(|k| k.as_str()).collect();
+                known.sort();
+                bail!(
+                    "Unknown post type `{kind}`, known types in `draft_templates`: [{}]",
+                    known.join(", ")
+                )
+            }
+        },
+        None => Ok(&cfg.draft_template),
+    }
+}
+
+// Date of a draft created at `now`, in `timezone`, shifted by `drafts_year_shift`
+fn draft_date(now: DateTime<Utc>, cfg: &SiteConfig) -> Result<DateTime<FixedOffset>> {
+    let date = now.with_timezone(&cfg.timezone);
+    let date = date.with_nanosecond(0).unwrap_or(date);
+    match date.with_year(date.year() + cfg.drafts_year_shift) {
+        Some(date) => Ok(date),
+        None => bail!(
+            "`drafts_year_shift` value `{}` gives an invalid date for {}",
+            cfg.drafts_year_shift,
+            date.date_naive()
+        ),
+    }
+}
+
+// `body` is added after the frontmatter, and the body of the template if any. Returns the path of
+// the draft.
+pub fn create_draft(
+    title: &str,
+    kind: Option<&str>,
+    body: Option<&str>,
+    description: Option<&str>,
+    cfg: &SiteConfig,
+) -> Result<PathBuf> {
+    let template = draft_template(kind, cfg)?;
 
-pub fn create_draft(title: &str, cfg: &SiteConfig) -> Result<()> {
     if !cfg.drafts_creation_dir.exists() {
         std::fs::create_dir_all(&cfg.drafts_creation_dir)?;
     }
 
-    let date = {
-        let today = Local::now();
-        let date = NaiveDate::from_ymd_opt(
-            today.year() + cfg.drafts_year_shift,
-            today.month(),
-            today.day(),
-        )
-        .expect(&format!(
-            "`drafts_year_shift` value `{}` made creation of chrono::NaiveDate fail",
-            cfg.drafts_year_shift
-        ))
-        .and_hms_opt(today.hour(), today.minute(), today.day())
-        .unwrap();
-        DateTime::from_naive_utc_and_offset(date, cfg.timezone)
-    };
+    let date = draft_date(Utc::now(), cfg)?;
 
     let slug = slugify(title);
     let filename = format!("{}.md", &slug);
@@ -35,30 +166,134 @@ pub fn create_draft(title: &str, cfg: &SiteConfig) -> Result<()> {
     if dest.exists() {
         bail!("file `{}` already exists.", filename);
     }
+    // drafts can be organized in subdirectories
+    if let Some(similar_file) = does_same_title_exist(&filename, &cfg.drafts_creation_dir)? {
+        bail!(
+            "a draft with the same title exists: `{}`",
+            similar_file.to_string_lossy()
+        );
+    }
+    if cfg.publish_dest.is_dir() {
+        if let Some(similar_file) = does_same_title_exist(&filename, &cfg.publish_dest)? {
+            warn!(
+                "A post with the same title is already published: `{}`",
+                similar_file.to_string_lossy()
+            );
+        }
+    }
 
-    let mut src = PathBuf::from("./templates/");
-    src.push(&cfg.draft_template);
+    let src = cfg.templates_dir.join(template);
     if src.exists() && !src.is_file() {
-        bail!("`{}` is not a file.", cfg.draft_template);
+        bail!("`{}` is not a file.", template);
+    }
+    if kind.is_some() && !src.exists() {
+        bail!("Template `{}` not found.", src.to_string_lossy());
+    }
+    let mut front = format!(
+        "+++\ntitle = {}\ndate = {}\ndraft = true\n",
+        toml::Value::from(title),
+        cfg.front_date(&date)
+    );
+    if let Some(description) = description {
+        front.push_str(&format!(
+            "description = {}\n",
+            toml::Value::from(description)
+        ));
     }
     let new_content = if src.exists() {
         modify_front(&src, |line: &str| {
-            if line.starts_with("+++") {
-                Ok(format!(
-                    "+++\ntitle = \"{title}\"\ndate = {}\ndraft = true\n",
-                    format_date(&date)
-                ))
+            if is_delimiter(line) {
+                Ok(front.clone())
             } else {
                 Ok(format!("{line}\n"))
             }
         })?
     } else {
-        format!(
-            "+++\ntitle = \"{title}\"\ndate = {}\ndraft = true\n+++\n",
-            format_date(&date)
-        )
+        format!("{front}+++\n")
+    };
+    let new_content = match body {
+        Some(body) => format!("{new_content}{body}"),
+        None => new_content,
     };
     std::fs::write(&dest, new_content)?;
-    println!("Success: post `{}` created.", &dest.to_string_lossy());
-    Ok(())
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Duration, FixedOffset, Timelike, Utc};
+
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::{create_draft, draft_date, parse_link_info, LinkInfo};
+
+    #[test]
+    fn test_draft_date() {
+        let now = Utc::now().with_nanosecond(0).unwrap();
+        let mut cfg = SiteConfig {
+            timezone: FixedOffset::east_opt(-5 * 3600).unwrap(),
+            ..Default::default()
+        };
+        let date = draft_date(now, &cfg).unwrap();
+        assert_eq!(date, now);
+        assert_eq!(date.offset(), &cfg.timezone);
+        assert_eq!(date.naive_local(), now.naive_utc() - Duration::hours(5));
+
+        cfg.drafts_year_shift = 2;
+        let date = draft_date(now, &cfg).unwrap();
+        assert_eq!(date.year(), now.with_timezone(&cfg.timezone).year() + 2);
+        assert_eq!(date.time(), now.with_timezone(&cfg.timezone).time());
+    }
+
+    #[test]
+    fn test_create_draft_nested_duplicate() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            drafts_creation_dir: root.join("drafts"),
+            publish_dest: root.join("posts"),
+            templates_dir: root.join("templates"),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(cfg.drafts_creation_dir.join("ideas/rust")).unwrap();
+        std::fs::write(
+            cfg.drafts_creation_dir.join("ideas/rust/my-post.md"),
+            "+++\ntitle = \"My post\"\n+++\n",
+        )
+        .unwrap();
+
+        let err = create_draft("My post", None, None, None, &cfg).unwrap_err();
+        assert!(err.to_string().contains("ideas/rust/my-post.md"), "{err}");
+        assert!(!cfg.drafts_creation_dir.join("my-post.md").exists());
+
+        create_draft("Another post", None, None, None, &cfg).unwrap();
+        assert!(cfg.drafts_creation_dir.join("another-post.md").exists());
+    }
+
+    #[test]
+    fn test_parse_link_info() {
+        let html = r#"<html><head>
+            <title>
+              Rust &amp; Zola
+            </title>
+            <meta name="description" content="Plain description">
+            <meta content='About &quot;emile&quot;' property='og:description' />
+        </head></html>"#;
+        assert_eq!(
+            parse_link_info(html),
+            LinkInfo {
+                title: Some("Rust & Zola".to_string()),
+                description: Some("About \"emile\"".to_string()),
+            }
+        );
+
+        let html = r#"<title></title><meta name="description" content="Plain">"#;
+        assert_eq!(
+            parse_link_info(html),
+            LinkInfo {
+                title: None,
+                description: Some("Plain".to_string()),
+            }
+        );
+    }
 }
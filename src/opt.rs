@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use emile::{config::SocialApi, export::DumpFormat};
 
 /// A workflow companion for zola (https://getzola.org)
 #[derive(Debug, Parser)]
@@ -9,40 +11,210 @@ pub struct Opt {
     /// Log directory
     #[arg(short, long, value_name = "DIR")]
     pub log_dir: Option<PathBuf>,
+    /// Rotation of the log file in the log directory
+    #[arg(long, value_enum, default_value_t = LogRotation::Never)]
+    pub log_rotation: LogRotation,
+    /// Name of the log file in the log directory, suffixed by the date when rotating
+    #[arg(long, value_name = "NAME", default_value = "emile.log")]
+    pub log_file: String,
+    /// Directory of an additional log file, in JSON lines
+    #[arg(long, value_name = "DIR")]
+    pub json_log_dir: Option<PathBuf>,
+    /// Name of the JSON log file in its directory, suffixed by the date when rotating
+    #[arg(long, value_name = "NAME", default_value = "emile.json")]
+    pub json_log_file: String,
+    /// Directory of the draft and social templates, overriding `templates_dir`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub template_dir: Option<PathBuf>,
+    /// Never contact the social servers, only log what would be posted. Posts are still moved
+    /// and the site built. Also enabled by the `EMILE_OFFLINE` env var
+    #[arg(long, global = true)]
+    pub no_network: bool,
+    /// Don't print the success messages of `new`, `publish` and `schedule`, only logged at debug
+    /// level, for scripts relying on the exit code
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum LogRotation {
+    Never,
+    Daily,
+    Hourly,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Create a new post in drafts folder, with current date prefiled in the frontmatter.
     /// The date can be modified with the `drafts_year_shift` configuration key
     #[command(visible_alias = "n")]
     New {
-        /// Title of the blog post. Needs to be around quotes.
-        title: String,
+        /// Title of the blog post. Needs to be around quotes. Optional with `--from`, taking the
+        /// title of the page
+        #[arg(required_unless_present = "from")]
+        title: Option<String>,
+        /// Type of post, selecting its template in the `draft_templates` config
+        #[arg(short = 't', long = "type")]
+        kind: Option<String>,
+        /// Read the body of the post from stdin
+        #[arg(long)]
+        stdin: bool,
+        /// Pre-fill the post with the title and description of the web page at this URL, and a
+        /// link to it
+        #[arg(long, value_name = "URL")]
+        from: Option<String>,
     },
     /// Mark a post as not draft, move it to `posts` folder, set the `date` field in front. It must
     /// be in the draft folder
     #[command(visible_alias = "p")]
     Publish {
-        /// Path to the post to publish
+        /// Path to the post to publish, or to a directory to publish all its posts
         post: PathBuf,
+        /// Directory to publish the post to, overriding `publish_dest`
+        #[arg(short, long, value_name = "PATH")]
+        dest: Option<PathBuf>,
+        /// URL of the website for the link in the social post, overriding `base_url`
+        #[arg(long, value_name = "URL")]
+        base_url: Option<String>,
+        /// Language of the social post, instead of the one deduced from the tags
+        #[arg(long, value_name = "CODE")]
+        lang: Option<String>,
+        /// Template of the social post, instead of `social_template`
+        #[arg(long, value_name = "PATH")]
+        template: Option<PathBuf>,
+        /// Keep the date of the draft, minus `drafts_year_shift`, instead of the current date
+        #[arg(long)]
+        keep_date: bool,
+        /// Build the site with the post first, and leave the draft untouched if it fails
+        #[arg(long)]
+        validate_build: bool,
+        /// Open the published post in the browser
+        #[arg(long)]
+        open: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
     },
     /// Launch watcher mode to manage scheduling and publication dynamically
     #[command(visible_alias = "w")]
     Watch {
         /// Path to the website to watch.
         website: PathBuf,
+        /// Only log what would be built and published
+        #[arg(long)]
+        dry_run: bool,
+        /// Append the builds and publications as JSON lines to this file
+        #[arg(long, value_name = "PATH")]
+        events_file: Option<PathBuf>,
+        /// Send the builds and publications as JSON lines to this listening unix socket
+        #[arg(long, value_name = "PATH", conflicts_with = "events_file")]
+        events_socket: Option<PathBuf>,
     },
     /// Schedule a post
     #[command(visible_alias = "s")]
     Schedule {
         /// When to publish the post. Can be relative to `now` ("tomorrow", "+3 days", "next week"),
         /// or absolute ("2024-06-27") (See the https://github.com/uutils/parse_datetime crate
-        /// for supported formats)
-        time: String,
-        /// Path to the post to publish
+        /// for supported formats). If omitted in a terminal, a few times are suggested to pick
+        /// from
+        time: Option<String>,
+        /// Paths to the posts to schedule, or to directories containing them
+        posts: Vec<PathBuf>,
+        /// Accept a date in the past or sooner than `min_schedule_lead_secs`
+        #[arg(long)]
+        now_ok: bool,
+        /// Slug of a post to publish after. `time` is then a delay after its publication, like
+        /// "+2 days" (minutes, hours, days or weeks)
+        #[arg(long, value_name = "SLUG")]
+        after: Option<String>,
+        /// Delay between two occurrences of a recurring post, like "1 week". The posts are then
+        /// templates, copied in the schedule directory for each occurrence
+        #[arg(long, value_name = "DELAY", conflicts_with = "after")]
+        repeat: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Schedule a draft at the date set in its frontmatter
+    #[command(visible_alias = "q")]
+    Queue {
+        /// Path to the post to schedule
         post: PathBuf,
     },
+    /// Delete the social media posts made when the post was published
+    Retract {
+        /// Path to the published post
+        post: PathBuf,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Post a text on the social media instances, without a blog post
+    Toot {
+        /// Text to post, as is
+        text: String,
+        /// Language of the social post, instead of the default language of the site
+        #[arg(long, value_name = "CODE")]
+        lang: Option<String>,
+    },
+    /// Log in each social instance without posting, to check its token still works
+    CheckTokens,
+    /// Post already published posts on the instances of a newly configured social network
+    Backfill {
+        /// Social network to post on, only on the instances the posts are not on yet
+        #[arg(value_enum)]
+        api: SocialApi,
+        /// Number of most recent posts to post
+        #[arg(short = 'n', long, default_value_t = 5)]
+        count: usize,
+    },
+    /// Show what a running `watch` is waiting for
+    Status,
+    /// Move a scheduled post back to the drafts directory, as a draft
+    Cancel {
+        /// Slug of the scheduled post, its file name without extension
+        slug: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List the drafts, oldest first
+    Drafts,
+    /// Report the placeholders of the social templates that are not filled, like a misspelled
+    /// `{titel}`
+    TemplateCheck,
+    /// Show the frontmatter fields of a post as emile reads them
+    Frontmatter {
+        /// Path to the post
+        post: PathBuf,
+    },
+    /// Rename posts after the slug of their title
+    Reslug {
+        /// Paths to the posts to rename, or directories to search for posts starting with `-`
+        #[arg(required = true)]
+        posts: Vec<PathBuf>,
+        /// Update the internal links (`@/…`) to the renamed posts in `content`
+        #[arg(long)]
+        fix_links: bool,
+        /// Add the old URL of the renamed posts to their `aliases`, so it keeps working
+        #[arg(long)]
+        keep_urls: bool,
+        /// Only rename the posts of the directories modified in this delay (ex: "2 days")
+        #[arg(long)]
+        since: Option<String>,
+        /// Only print the renames, and the posts that can't be renamed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export the scheduled posts, to be imported in a calendar for example
+    DumpSchedule {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = DumpFormat::Json)]
+        format: DumpFormat,
+        /// File to write the export to, instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
 }
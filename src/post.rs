@@ -1,4 +1,5 @@
 use std::{
+    ffi::OsString,
     fs::File,
     io::{BufRead, BufReader},
     path::Path,
@@ -9,6 +10,29 @@ use chrono::{DateTime, FixedOffset, NaiveDate};
 
 use crate::config::SiteConfig;
 
+// `[extra]` keys of a post scheduled with `schedule --after`, until the post it depends on is
+// published
+pub const AFTER_KEY: &str = "emile_after";
+pub const AFTER_DELAY_KEY: &str = "emile_after_delay";
+
+// Whether `path` is the `_index.md` of a section, or one of its translations (`_index.fr.md`)
+pub fn is_section_index(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| {
+            let name = name.to_string_lossy();
+            name.split('.').next() == Some("_index")
+        })
+        .unwrap_or(false)
+}
+
+// `+++` line delimiting the frontmatter, tolerating leading whitespace and the UTF-8 BOM some
+// editors put at the start of files
+pub fn is_delimiter(line: &str) -> bool {
+    line.trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with("+++")
+}
+
 pub fn modify_front(
     path: &Path,
     mut operation: impl FnMut(&str) -> Result<String>,
@@ -18,10 +42,16 @@ pub fn modify_front(
     let mut new_content = String::new();
     let mut in_frontmatter = true;
     let mut nb_sep = 0;
-    for line in reader.lines() {
+    for (i, line) in reader.lines().enumerate() {
         let line = line.expect("Should have text");
+        // the BOM is dropped from the rewritten file
+        let line = if i == 0 {
+            line.trim_start_matches('\u{feff}').to_owned()
+        } else {
+            line
+        };
         if in_frontmatter {
-            if line.starts_with("+++") {
+            if is_delimiter(&line) {
                 nb_sep += 1;
             }
 
@@ -45,6 +75,223 @@ pub fn modify_front(
     }
 }
 
+// Frontmatter of `content`, without the `+++` delimiters
+pub fn frontmatter(content: &str) -> Result<String> {
+    let mut front = String::new();
+    let mut nb_sep = 0;
+    for line in content.lines() {
+        if is_delimiter(line) {
+            nb_sep += 1;
+            if nb_sep >= 2 {
+                return Ok(front);
+            }
+        } else if nb_sep == 1 {
+            front.push_str(line);
+            front.push('\n');
+        }
+    }
+    bail!("Missing `+++` delimiter")
+}
+
+// Whether the frontmatter has `draft = true`, Zola not building the post then
+pub fn is_draft(content: &str) -> bool {
+    frontmatter(content)
+        .ok()
+        .and_then(|front| toml::from_str::<toml::Table>(&front).ok())
+        .and_then(|front| front.get("draft")?.as_bool())
+        .unwrap_or(false)
+}
+
+// Whether the frontmatter is valid and says the post is not a draft, `draft = false` or no `draft`
+// at all. A file being written, with an incomplete frontmatter, is not.
+pub fn is_undrafted(content: &str) -> bool {
+    frontmatter(content)
+        .ok()
+        .and_then(|front| toml::from_str::<toml::Table>(&front).ok())
+        .map(|front| front.get("draft").and_then(|draft| draft.as_bool()) != Some(true))
+        .unwrap_or(false)
+}
+
+// File name of the post once published: `<slug>.<ext>` if the frontmatter has a `slug`, as Zola
+// uses it for the URL, or the file name of `post` otherwise
+pub fn dest_filename(post: &Path, content: &str) -> OsString {
+    let slug = frontmatter(content)
+        .ok()
+        .and_then(|front| toml::from_str::<toml::Table>(&front).ok())
+        .and_then(|front| front.get("slug")?.as_str().map(|s| s.trim().to_string()))
+        .filter(|slug| !slug.is_empty());
+    match slug {
+        Some(slug) => {
+            let ext = post.extension().unwrap_or("md".as_ref()).to_string_lossy();
+            OsString::from(format!("{slug}.{ext}"))
+        }
+        None => post
+            .file_name()
+            .expect("a Post can’t be without a file name")
+            .to_os_string(),
+    }
+}
+
+// `weight` of the frontmatter, as Zola uses to sort the pages of a section
+pub fn weight(content: &str) -> Option<i64> {
+    frontmatter(content)
+        .ok()
+        .and_then(|front| toml::from_str::<toml::Table>(&front).ok())
+        .and_then(|front| front.get("weight")?.as_integer())
+}
+
+// Slug of the post that must be published before this one, and the delay to wait after it
+pub fn after_dependency(content: &str) -> Option<(String, String)> {
+    let front = toml::from_str::<toml::Table>(&frontmatter(content).ok()?).ok()?;
+    let extra = front.get("extra")?;
+    let after = extra.get(AFTER_KEY)?.as_str()?.to_string();
+    let delay = extra.get(AFTER_DELAY_KEY)?.as_str()?.to_string();
+    Some((after, delay))
+}
+
+fn is_key(line: &str, key: &str) -> bool {
+    line.strip_prefix(key)
+        .map(|rest| rest.trim_start().starts_with('='))
+        .unwrap_or(false)
+}
+
+// Set `key = value` at the top level of the frontmatter, replacing the existing value or inserting
+// it before the first table
+pub fn set_front_field(content: &str, key: &str, value: &str) -> Result<String> {
+    let mut new_content = String::new();
+    let mut nb_sep = 0;
+    let mut in_table = false;
+    let mut done = false;
+    for line in content.lines() {
+        if nb_sep == 1 && !done {
+            let trimmed = line.trim();
+            if is_delimiter(line) || trimmed.starts_with('[') {
+                new_content.push_str(&format!("{key} = {value}\n"));
+                done = true;
+                in_table = true;
+            } else if !in_table && is_key(trimmed, key) {
+                new_content.push_str(&format!("{key} = {value}\n"));
+                done = true;
+                continue;
+            }
+        }
+
+        if is_delimiter(line) {
+            nb_sep += 1;
+        }
+        new_content.push_str(line);
+        new_content.push('\n');
+    }
+
+    if nb_sep < 2 {
+        bail!("Missing `+++` delimiter")
+    } else {
+        Ok(new_content)
+    }
+}
+
+// Set `key = value` in the `[extra]` table of the frontmatter, creating the table if needed. A
+// `None` value removes the key.
+pub fn set_extra_field(content: &str, key: &str, value: Option<&str>) -> Result<String> {
+    let field = value.map(|value| format!("{key} = {value}\n"));
+    let mut new_content = String::new();
+    let mut nb_sep = 0;
+    let mut in_extra = false;
+    let mut done = field.is_none();
+    for line in content.lines() {
+        if nb_sep == 1 {
+            let trimmed = line.trim();
+            if is_delimiter(line) {
+                if let Some(field) = field.as_ref().filter(|_| !done) {
+                    if !in_extra {
+                        new_content.push_str("[extra]\n");
+                    }
+                    new_content.push_str(field);
+                    done = true;
+                }
+            } else if trimmed.starts_with('[') {
+                if let Some(field) = field.as_ref().filter(|_| in_extra && !done) {
+                    new_content.push_str(field);
+                    done = true;
+                }
+                in_extra = trimmed == "[extra]";
+            } else if in_extra && is_key(trimmed, key) {
+                // replace the existing value
+                if let Some(field) = field.as_ref().filter(|_| !done) {
+                    new_content.push_str(field);
+                    done = true;
+                }
+                continue;
+            }
+        }
+
+        if is_delimiter(line) {
+            nb_sep += 1;
+        }
+        new_content.push_str(line);
+        new_content.push('\n');
+    }
+
+    if nb_sep < 2 {
+        bail!("Missing `+++` delimiter")
+    } else {
+        Ok(new_content)
+    }
+}
+
+// Fields of `required`, as dotted keys like `taxonomies.tags`, missing or empty in the frontmatter
+pub fn missing_front_fields<'a>(content: &str, required: &'a [String]) -> Vec<&'a str> {
+    let front = frontmatter(content)
+        .ok()
+        .and_then(|front| toml::from_str::<toml::Table>(&front).ok())
+        .map(toml::Value::Table);
+    required
+        .iter()
+        .map(String::as_str)
+        .filter(|field| {
+            let value = front.as_ref().and_then(|front| {
+                field
+                    .split('.')
+                    .try_fold(front, |value, key| value.get(key))
+            });
+            match value {
+                None => true,
+                Some(toml::Value::String(s)) => s.trim().is_empty(),
+                Some(toml::Value::Array(values)) => values.is_empty(),
+                Some(toml::Value::Table(table)) => table.is_empty(),
+                Some(_) => false,
+            }
+        })
+        .collect()
+}
+
+// Fail if the frontmatter lacks one of the `required_front_fields`
+pub fn check_required_fields(content: &str, cfg: &SiteConfig) -> Result<()> {
+    let missing = missing_front_fields(content, &cfg.required_front_fields);
+    if !missing.is_empty() {
+        bail!(
+            "Missing required frontmatter field(s): `{}`",
+            missing.join("`, `")
+        );
+    }
+    Ok(())
+}
+
+// Parse a frontmatter `date`, either RFC3339 or a date only, taken at midnight in `timezone`
+pub fn parse_front_date(date_str: &str, timezone: FixedOffset) -> Result<DateTime<FixedOffset>> {
+    if date_str.len() == 10 {
+        let date_time = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?
+            .and_hms_opt(0, 0, 0)
+            .expect("Creation of NaiveDateTime blew up");
+        match date_time.and_local_timezone(timezone).single() {
+            Some(date) => Ok(date),
+            None => bail!("Invalid `date` `{date_str}`"),
+        }
+    } else {
+        Ok(DateTime::parse_from_rfc3339(date_str)?)
+    }
+}
+
 pub fn extract_date(path: &Path, cfg: &SiteConfig) -> Result<DateTime<FixedOffset>> {
     let file = File::open(path)?;
     let reader = BufReader::new(&file);
@@ -53,7 +300,7 @@ pub fn extract_date(path: &Path, cfg: &SiteConfig) -> Result<DateTime<FixedOffse
     for line in reader.lines() {
         let line = line.expect("Should have text");
         if in_front {
-            if line.starts_with("+++") {
+            if is_delimiter(&line) {
                 nb_sep += 1;
                 if nb_sep >= 2 {
                     in_front = false;
@@ -64,15 +311,7 @@ pub fn extract_date(path: &Path, cfg: &SiteConfig) -> Result<DateTime<FixedOffse
                     bail!("Invalid `date`");
                 }
                 let date_str = date_split.get(1).unwrap().trim();
-                let date = if date_str.len() == 10 {
-                    let date_time = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?
-                        .and_hms_opt(0, 0, 0)
-                        .expect("Creation of NaiveDateTime blew up");
-                    DateTime::from_naive_utc_and_offset(date_time, cfg.timezone)
-                } else {
-                    DateTime::parse_from_rfc3339(date_str)?
-                };
-                return Ok(date);
+                return parse_front_date(date_str, cfg.timezone);
             }
         } else {
             bail!("No `date` in frontmatter")
@@ -80,3 +319,98 @@ pub fn extract_date(path: &Path, cfg: &SiteConfig) -> Result<DateTime<FixedOffse
     }
     bail!("No `date` in frontmatter")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::FixedOffset;
+
+    use super::{
+        dest_filename, is_undrafted, missing_front_fields, parse_front_date, set_extra_field,
+    };
+
+    #[test]
+    fn test_missing_front_fields() {
+        let required = [
+            "description".to_string(),
+            "taxonomies.tags".to_string(),
+            "extra.cover".to_string(),
+        ];
+        let content =
+            "+++\ntitle = \"Post\"\ndescription = \"About\"\n[taxonomies]\ntags = [\"rust\"]\n\
+                       [extra]\ncover = \"cover.png\"\n+++\n";
+        assert!(missing_front_fields(content, &required).is_empty());
+        let content = "+++\ntitle = \"Post\"\ndescription = \" \"\n[taxonomies]\ntags = []\n+++\n";
+        assert_eq!(
+            missing_front_fields(content, &required),
+            ["description", "taxonomies.tags", "extra.cover"]
+        );
+        assert!(missing_front_fields(content, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_is_undrafted() {
+        assert!(is_undrafted("+++\ntitle = \"Post\"\ndraft = false\n+++\n"));
+        assert!(is_undrafted("+++\ntitle = \"Post\"\n+++\n"));
+        assert!(!is_undrafted("+++\ntitle = \"Post\"\ndraft = true\n+++\n"));
+        // being written
+        assert!(!is_undrafted("+++\ntitle = \"Po"));
+        assert!(!is_undrafted("+++\ntitle = \"Post\"\ndraft = fa\n+++\n"));
+    }
+
+    #[test]
+    fn test_parse_front_date() {
+        let timezone = FixedOffset::west_opt(5 * 3600).unwrap();
+        // a date only is midnight in the site's timezone, so it keeps its day when written back
+        let date = parse_front_date("2024-03-05", timezone).unwrap();
+        assert_eq!(date.to_rfc3339(), "2024-03-05T00:00:00-05:00");
+        assert_eq!(date.format("%Y-%m-%d").to_string(), "2024-03-05");
+        let date = parse_front_date("2024-03-05T10:00:00+02:00", timezone).unwrap();
+        assert_eq!(date.to_rfc3339(), "2024-03-05T10:00:00+02:00");
+        assert!(parse_front_date("05/03/2024", timezone).is_err());
+    }
+
+    #[test]
+    fn test_dest_filename_with_slug() {
+        let content = "+++\ntitle = \"Year in review\"\nslug = \"year-in-review\"\n+++\n";
+        assert_eq!(
+            dest_filename(Path::new("content/drafts/2024-notes.md"), content),
+            "year-in-review.md"
+        );
+    }
+
+    #[test]
+    fn test_dest_filename_without_slug() {
+        let content = "+++\ntitle = \"Year in review\"\n[extra]\nslug = \"other\"\n+++\n";
+        assert_eq!(
+            dest_filename(Path::new("content/drafts/2024-notes.md"), content),
+            "2024-notes.md"
+        );
+    }
+
+    #[test]
+    fn test_set_extra_field() {
+        let content = "+++\ntitle = \"Post\"\n+++\nbody\n";
+        let with_extra = set_extra_field(content, "key", Some("\"value\"")).unwrap();
+        assert_eq!(
+            with_extra,
+            "+++\ntitle = \"Post\"\n[extra]\nkey = \"value\"\n+++\nbody\n"
+        );
+
+        let replaced = set_extra_field(&with_extra, "key", Some("\"other\"")).unwrap();
+        assert_eq!(
+            replaced,
+            "+++\ntitle = \"Post\"\n[extra]\nkey = \"other\"\n+++\nbody\n"
+        );
+
+        let removed = set_extra_field(&replaced, "key", None).unwrap();
+        assert_eq!(removed, "+++\ntitle = \"Post\"\n[extra]\n+++\nbody\n");
+
+        let content = "+++\n[extra]\nlang = \"fr\"\n[taxonomies]\ntags = []\n+++\n";
+        assert_eq!(
+            set_extra_field(content, "key", Some("1")).unwrap(),
+            "+++\n[extra]\nlang = \"fr\"\nkey = 1\n[taxonomies]\ntags = []\n+++\n"
+        );
+    }
+}
@@ -1,20 +1,77 @@
-use std::fs::{self, DirEntry};
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
-use chrono::Utc;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use tracing::{debug, error, info};
 
-use crate::config::SiteConfig;
-use crate::format_date;
-use crate::post::modify_front;
-use crate::social::push_to_social;
+use crate::config::{PublishDraftBehavior, SiteConfig};
+use crate::error::{Categorize, ErrorCategory};
+use crate::post::{
+    check_required_fields, dest_filename, is_section_index, modify_front, set_front_field,
+};
+use crate::scheduler::schedule_dependents;
+use crate::social::{http_client, post_lang, push_to_social};
+use crate::{format_date, zola_build};
 
-pub async fn publish_post(post: &Path, cfg: &SiteConfig) -> Result<String> {
+// Who asked for the publication, for the automated path to have its own policy
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PublishSource {
+    // `publish` command or library call
+    #[default]
+    Manual,
+    // `watch` publishing a scheduled post
+    Scheduled,
+}
+
+#[derive(Debug, Default)]
+pub struct PublishOptions {
+    // language of the social post, instead of the one deduced from the tags
+    pub lang: Option<String>,
+    // template of the social post, instead of `social_template`
+    pub template: Option<PathBuf>,
+    // keep the `date` of the draft, minus `drafts_year_shift`, instead of the publication date
+    pub keep_date: bool,
+    // build the site with the post before publishing it, leaving the draft as is if it fails
+    pub validate_build: bool,
+    pub source: PublishSource,
+}
+
+// Remove the `drafts_year_shift` years added to the date of a draft by `new`
+fn unshift_date(value: &str, shift: i32) -> Result<String> {
+    let value = value.trim();
+    let months = Months::new(shift.unsigned_abs() * 12);
+    let unshifted = if value.len() == 10 {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")?;
+        if shift > 0 {
+            date.checked_sub_months(months)
+        } else {
+            date.checked_add_months(months)
+        }
+        .map(|date| date.format("%Y-%m-%d").to_string())
+    } else {
+        let date = DateTime::parse_from_rfc3339(value)?;
+        if shift > 0 {
+            date.checked_sub_months(months)
+        } else {
+            date.checked_add_months(months)
+        }
+        .map(|date| format_date(&date))
+    };
+    unshifted.with_context(|| format!("Failed to remove {shift} year(s) from `{value}`"))
+}
+
+pub async fn publish_post(post: &Path, cfg: &SiteConfig, opts: &PublishOptions) -> Result<String> {
     if !post.exists() {
         bail!("`{}` doesn't exist", post.to_string_lossy());
     }
 
-    if !(post.starts_with(&cfg.drafts_creation_dir) || post.starts_with(&cfg.schedule_dir)) {
+    // the directories are canonicalized when the config is read, if they exist
+    let is_in = |dir: &Path| {
+        post.canonicalize()
+            .is_ok_and(|post| dir.canonicalize().is_ok_and(|dir| post.starts_with(dir)))
+    };
+    if !(is_in(&cfg.drafts_creation_dir) || is_in(&cfg.schedule_dir)) {
         bail!(
             "Post to be published must be in `{}` or `{}`",
             cfg.drafts_creation_dir.to_string_lossy(),
@@ -22,69 +79,465 @@ pub async fn publish_post(post: &Path, cfg: &SiteConfig) -> Result<String> {
         );
     }
 
+    if !cfg.is_markdown(post) {
+        bail!(
+            "Post must be a markdown file with one of the `{}` extensions",
+            cfg.markdown_extensions.join("`, `")
+        );
+    }
+
+    if is_section_index(post) {
+        bail!(
+            "`{}` is the index of a section, not a post",
+            post.to_string_lossy()
+        );
+    }
+    check_required_fields(&fs::read_to_string(post)?, cfg)
+        .with_context(|| format!("`{}` can't be published", post.to_string_lossy()))?;
+
     let date = Utc::now().with_timezone(&cfg.timezone);
-    let new_content = modify_front(&post, |cur_line: &str| {
+    let mut has_draft = false;
+    let new_content = modify_front(post, |cur_line: &str| {
         let modified = if cur_line.starts_with("date = ") {
-            // modify date
-            format!("date = {}\n", format_date(&date))
+            if opts.keep_date {
+                if cfg.drafts_year_shift == 0 {
+                    format!("{cur_line}\n")
+                } else {
+                    let value = cur_line.trim_start_matches("date = ");
+                    format!("date = {}\n", unshift_date(value, cfg.drafts_year_shift)?)
+                }
+            } else {
+                // modify date
+                format!("date = {}\n", cfg.front_date(&date))
+            }
         } else if !cur_line.starts_with("draft =") {
             // don’t modify
             format!("{cur_line}\n")
         } else {
-            // delete `draft` line
-            "".to_string()
+            has_draft = true;
+            match cfg.publish_draft_behavior {
+                // delete `draft` line
+                PublishDraftBehavior::Remove => "".to_string(),
+                PublishDraftBehavior::SetFalse => "draft = false\n".to_string(),
+            }
         };
         Ok(modified)
     })?;
-    let filename = post
-        .file_name()
-        .expect("a Post can’t be without a file name");
-    let dest = cfg.publish_dest.join(&filename);
+    let new_content = if cfg.publish_draft_behavior == PublishDraftBehavior::SetFalse && !has_draft
+    {
+        set_front_field(&new_content, "draft", "false")?
+    } else {
+        new_content
+    };
+    let new_content = if cfg.set_updated_on_publish {
+        set_front_field(&new_content, "updated", &cfg.front_date(&date))?
+    } else {
+        new_content
+    };
+    let dest = resolve_dest(post, &new_content, cfg);
+    let publish_dest = dest.parent().unwrap_or(&cfg.publish_dest);
+    let filename = dest.file_name().unwrap_or_default();
     if dest.exists() {
         bail!("file {} already exists.", dest.to_string_lossy());
     }
 
-    if let Some(similar_file) =
-        does_same_title_exist(&filename.to_string_lossy(), &cfg.publish_dest)?
-    {
+    if let Some(similar_file) = does_same_title_exist(&filename.to_string_lossy(), publish_dest)? {
         bail!(
             "Warning: a post with a the same title exists: `{}`",
-            similar_file.file_name().to_string_lossy()
+            similar_file.to_string_lossy()
         );
     }
 
-    if let Some(social_cfg) = cfg.social.as_ref() {
-        match push_to_social(social_cfg, &new_content, &dest).await {
-            Ok(new_content) => {
-                fs::write(&dest, &new_content)?;
-                fs::remove_file(&post)?;
-            }
-            Err(e) => {
+    if opts.validate_build {
+        validate_build(&dest, &new_content, cfg)?;
+    }
+
+    let (new_content, social_err) = match cfg.social.as_ref() {
+        Some(social_cfg) => {
+            let pushed = match http_client(social_cfg) {
+                Ok(client) => {
+                    push_to_social(
+                        &client,
+                        social_cfg,
+                        &new_content,
+                        &dest,
+                        opts.lang.as_deref(),
+                        opts.template.as_deref(),
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+            match pushed {
+                Ok((new_content, 0)) => (new_content, None),
+                // the links and records of the social posts that succeeded are kept
+                Ok((new_content, nb_failed)) => (
+                    new_content,
+                    Some(anyhow!("{nb_failed} social post(s) failed, see the log")),
+                ),
                 // write the post even if social media failed
-                fs::write(&dest, &new_content)?;
-                fs::remove_file(&post)?;
-                return Err(e);
+                Err(e) => (new_content, Some(e)),
             }
         }
-    } else {
-        fs::write(&dest, &new_content)?;
-        fs::remove_file(&post)?;
+        None => (new_content, None),
+    };
+    fs::write(&dest, &new_content)?;
+    fs::remove_file(post)?;
+
+    let slug = Path::new(&filename).file_stem().unwrap_or_default();
+    if let Err(err) = schedule_dependents(&slug.to_string_lossy(), &date, cfg) {
+        error!("Failed to schedule the posts waiting for `{dest:?}`: {err:#}");
     }
 
-    Ok(dest.to_string_lossy().to_string())
+    match opts.source {
+        PublishSource::Manual => info!("Published `{}`", dest.to_string_lossy()),
+        PublishSource::Scheduled => debug!("Published `{}`", dest.to_string_lossy()),
+    }
+
+    match social_err {
+        Some(e) => Err(e).categorize(ErrorCategory::Social),
+        None => Ok(dest.to_string_lossy().to_string()),
+    }
 }
 
-pub fn does_same_title_exist(filename: &str, dir: &Path) -> Result<Option<DirEntry>> {
-    if let Some(res) = fs::read_dir(dir)?.find(|f| {
-        let f = f.as_ref().expect("Should have a valid entry");
-        if f.file_type().expect("Should have a FileType").is_file() {
-            f.file_name().to_string_lossy().contains(filename)
-        } else {
-            false
+// Final path of `post` once published, in the destination of the language found in its
+// frontmatter and named after its `slug` if it has one. `--lang` only changes the social post, not
+// where the post goes. It is resolved before posting on social media, the link in the social post
+// being made from it.
+pub fn resolve_dest(post: &Path, content: &str, cfg: &SiteConfig) -> PathBuf {
+    let lang = cfg
+        .social
+        .as_ref()
+        .and_then(|social_cfg| post_lang(content, social_cfg));
+    cfg.publish_dest_for(lang.as_deref())
+        .join(dest_filename(post, content))
+}
+
+// Publish the posts of `dir`, in the order of their names, a failure not stopping the others.
+// Returns the result of `publish_post` for each post, the site being left to build once by the
+// caller.
+pub async fn publish_dir(
+    dir: &Path,
+    cfg: &SiteConfig,
+    opts: &PublishOptions,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    let mut posts: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && cfg.is_post(path))
+        .collect();
+    posts.sort();
+
+    let mut results = Vec::new();
+    for post in posts {
+        let res = publish_post(&post, cfg, opts).await;
+        results.push((post, res));
+    }
+    Ok(results)
+}
+
+// Build the site with the post written at `dest`, where it is removed from if the build fails
+fn validate_build(dest: &Path, content: &str, cfg: &SiteConfig) -> Result<()> {
+    fs::write(dest, content)?;
+    if let Err(err) = zola_build(cfg) {
+        fs::remove_file(dest)?;
+        return Err(err.context("The site doesn't build with the post, it is not published"));
+    }
+    Ok(())
+}
+
+// First file of `dir` or of its subdirectories whose name contains `filename`
+pub fn does_same_title_exist(filename: &str, dir: &Path) -> Result<Option<PathBuf>> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            if let Some(similar_file) = does_same_title_exist(filename, &entry.path())? {
+                return Ok(Some(similar_file));
+            }
+        } else if entry.file_name().to_string_lossy().contains(filename) {
+            return Ok(Some(entry.path()));
         }
-    }) {
-        Ok(Some(res.expect("Should have DirEntry")))
-    } else {
-        Ok(None)
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        config::{PublishDraftBehavior, SiteConfig, TagCase},
+        social::{self, tests::social_cfg},
+        test_dir,
+    };
+
+    use super::{publish_dir, publish_post, resolve_dest, unshift_date, PublishOptions};
+
+    // Publish from `root/drafts` to `root/posts`
+    fn publish_cfg(root: &Path) -> SiteConfig {
+        let cfg = SiteConfig {
+            drafts_creation_dir: root.join("drafts"),
+            publish_dest: root.join("posts"),
+            ..Default::default()
+        };
+        fs::create_dir_all(&cfg.drafts_creation_dir).unwrap();
+        fs::create_dir_all(&cfg.publish_dest).unwrap();
+        cfg
+    }
+
+    #[test]
+    fn test_unshift_date() {
+        assert_eq!(unshift_date("2025-06-27", 1).unwrap(), "2024-06-27");
+        assert_eq!(
+            unshift_date("2025-06-27T12:00:00+02:00", 1).unwrap(),
+            "2024-06-27T12:00:00+02:00"
+        );
+        assert_eq!(unshift_date("2023-06-27", -1).unwrap(), "2024-06-27");
+        assert!(unshift_date("June 27", 1).is_err());
+    }
+
+    #[test]
+    fn test_link_from_slug() {
+        let cfg = SiteConfig {
+            social: Some(social_cfg(TagCase::Pascal)),
+            ..Default::default()
+        };
+        let content = "+++\ntitle = \"Post\"\nslug = \"final-slug\"\n+++\n";
+        let dest = resolve_dest(Path::new("content/drafts/draft-name.md"), content, &cfg);
+        assert_eq!(dest, Path::new("content/posts/final-slug.md"));
+        // the link of the social post is the URL of the published file
+        assert_eq!(
+            social::post_link(cfg.social.as_ref().unwrap(), &dest),
+            "https://example.com/posts/final-slug/"
+        );
+    }
+
+    #[test]
+    fn test_lang_dest() {
+        let mut social = social_cfg(TagCase::Pascal);
+        social.lang_key = Some("extra.lang".to_string());
+        let cfg = SiteConfig {
+            publish_dest: PathBuf::from("content/posts"),
+            lang_publish_dest: HashMap::from([(
+                "fr".to_string(),
+                PathBuf::from("content/posts.fr"),
+            )]),
+            social: Some(social),
+            ..Default::default()
+        };
+        let draft = Path::new("content/drafts/post.md");
+        let content = "+++\ntitle = \"Post\"\n[extra]\nlang = \"fr\"\n+++\n";
+        assert_eq!(
+            resolve_dest(draft, content, &cfg),
+            Path::new("content/posts.fr/post.md")
+        );
+        let content = "+++\ntitle = \"Post\"\n+++\n";
+        assert_eq!(
+            resolve_dest(draft, content, &cfg),
+            Path::new("content/posts/post.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish_section_index() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = publish_cfg(root);
+
+        for name in ["_index.md", "_index.fr.md"] {
+            let index = cfg.drafts_creation_dir.join(name);
+            fs::write(&index, "+++\ntitle = \"Drafts\"\n+++\n").unwrap();
+            let err = publish_post(&index, &cfg, &PublishOptions::default())
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("index of a section"), "{err}");
+            // left untouched
+            assert!(index.is_file());
+        }
+        assert_eq!(fs::read_dir(&cfg.publish_dest).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_keep_date_with_year_shift() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            drafts_year_shift: 1,
+            ..publish_cfg(root)
+        };
+
+        let post = cfg.drafts_creation_dir.join("post.md");
+        fs::write(
+            &post,
+            "+++\ntitle = \"Post\"\ndate = 2025-06-27T12:00:00+00:00\ndraft = true\n+++\n",
+        )
+        .unwrap();
+        let opts = PublishOptions {
+            keep_date: true,
+            ..Default::default()
+        };
+        let dest = publish_post(&post, &cfg, &opts).await.unwrap();
+
+        assert_eq!(
+            fs::read_to_string(dest).unwrap(),
+            "+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+00:00\n+++\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_toot_lang_keeps_dest() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            lang_publish_dest: HashMap::from([("fr".to_string(), root.join("posts.fr"))]),
+            ..publish_cfg(root)
+        };
+        fs::create_dir_all(root.join("posts.fr")).unwrap();
+
+        let draft = "+++\ntitle = \"Post\"\ndate = 2024-01-01\ndraft = true\n+++\n";
+        let post = cfg.drafts_creation_dir.join("post.md");
+        fs::write(&post, draft).unwrap();
+        // the language of the social post doesn't move the post out of its destination
+        let opts = PublishOptions {
+            lang: Some("fr".to_string()),
+            ..Default::default()
+        };
+        let dest = publish_post(&post, &cfg, &opts).await.unwrap();
+        assert_eq!(dest, root.join("posts/post.md").to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn test_publish_dir() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = publish_cfg(root);
+        let batch = cfg.drafts_creation_dir.join("batch");
+        fs::create_dir_all(&batch).unwrap();
+        let draft = "+++\ntitle = \"Post\"\ndate = 2024-01-01\n+++\n";
+        for name in ["a.md", "b.md", "c.md", "notes.txt", "_index.md"] {
+            fs::write(batch.join(name), draft).unwrap();
+        }
+        // `b.md` can't be published, without stopping `c.md`
+        fs::write(cfg.publish_dest.join("b.md"), draft).unwrap();
+
+        let results = publish_dir(&batch, &cfg, &PublishOptions::default())
+            .await
+            .unwrap();
+        let published: Vec<_> = results
+            .iter()
+            .map(|(post, res)| (post.file_name().unwrap().to_str().unwrap(), res.is_ok()))
+            .collect();
+        assert_eq!(published, [("a.md", true), ("b.md", false), ("c.md", true)]);
+        assert!(cfg.publish_dest.join("c.md").is_file());
+        assert!(batch.join("b.md").is_file());
+    }
+
+    async fn publish_draft(behavior: PublishDraftBehavior, front: &str) -> String {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            publish_draft_behavior: behavior,
+            ..publish_cfg(root)
+        };
+
+        let post = cfg.drafts_creation_dir.join("post.md");
+        fs::write(&post, format!("+++\n{front}[taxonomies]\ntags = []\n+++\n")).unwrap();
+        let dest = publish_post(&post, &cfg, &PublishOptions::default())
+            .await
+            .unwrap();
+        fs::read_to_string(dest).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_publish_draft_behavior() {
+        let with_draft = "title = \"Post\"\ndraft = true\n";
+        let without_draft = "title = \"Post\"\n";
+        let set_false = "+++\ntitle = \"Post\"\ndraft = false\n[taxonomies]\ntags = []\n+++\n";
+
+        let content = publish_draft(PublishDraftBehavior::Remove, with_draft).await;
+        assert!(!content.contains("draft"), "{content}");
+        let content = publish_draft(PublishDraftBehavior::Remove, without_draft).await;
+        assert!(!content.contains("draft"), "{content}");
+
+        let content = publish_draft(PublishDraftBehavior::SetFalse, with_draft).await;
+        assert_eq!(content, set_false);
+        let content = publish_draft(PublishDraftBehavior::SetFalse, without_draft).await;
+        assert_eq!(content, set_false);
+    }
+
+    #[tokio::test]
+    async fn test_publish_with_bom() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = publish_cfg(root);
+
+        let post = cfg.drafts_creation_dir.join("imported-post.md");
+        fs::write(&post, include_str!("../tests/fixtures/bom_draft.md")).unwrap();
+        let dest = publish_post(&post, &cfg, &PublishOptions::default())
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(dest).unwrap();
+        assert!(
+            content.starts_with("+++\ntitle = \"Imported post\"\n"),
+            "{content}"
+        );
+        assert!(!content.contains("draft"));
+        assert!(content.ends_with("+++\n\nWritten in another editor.\n"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_build() {
+        let dir = test_dir();
+        let root = dir.path();
+        let mut cfg = SiteConfig {
+            build_command: vec!["false".to_string()],
+            ..publish_cfg(root)
+        };
+
+        let draft = "+++\ntitle = \"Post\"\ndraft = true\n+++\n";
+        let post = cfg.drafts_creation_dir.join("post.md");
+        fs::write(&post, draft).unwrap();
+        let opts = PublishOptions {
+            validate_build: true,
+            ..Default::default()
+        };
+        assert!(publish_post(&post, &cfg, &opts).await.is_err());
+        assert_eq!(fs::read_to_string(&post).unwrap(), draft);
+        assert!(!cfg.publish_dest.join("post.md").exists());
+
+        cfg.build_command = vec!["true".to_string()];
+        let dest = publish_post(&post, &cfg, &opts).await.unwrap();
+        assert_eq!(dest, root.join("posts/post.md").to_string_lossy());
+        assert!(!post.exists());
+    }
+
+    #[tokio::test]
+    async fn test_set_updated_on_publish() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            set_updated_on_publish: true,
+            ..publish_cfg(root)
+        };
+
+        let post = cfg.drafts_creation_dir.join("post.md");
+        fs::write(
+            &post,
+            "+++\ntitle = \"Post\"\ndate = 2024-01-01\ndraft = true\n[taxonomies]\ntags = []\n+++\nbody\n",
+        )
+        .unwrap();
+        let dest = publish_post(&post, &cfg, &PublishOptions::default())
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(dest).unwrap();
+        let front: Vec<_> = content.lines().collect();
+        assert!(front[3].starts_with("updated = "), "{content}");
+        assert_eq!(front[4], "[taxonomies]");
+        assert!(!content.contains("draft"));
     }
 }
@@ -0,0 +1,192 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_derive::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    config::SiteConfig,
+    describe_date, format_date,
+    post::set_front_field,
+    scheduler::{parse_delay, scheduled_content},
+};
+
+// written in the website's directory by `schedule --repeat`, read by `watch` to schedule the next
+// occurrence of a recurring post once the current one is published
+pub const REPEAT_FILE: &str = ".emile-repeat.json";
+
+// Placeholder of the template replaced by the date of the occurrence
+const DATE_PLACEHOLDER: &str = "{date}";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Recurrence {
+    // post copied in `schedule_dir` for each occurrence
+    template: PathBuf,
+    // delay between two occurrences, like "1 week"
+    every: String,
+    // file name, in `schedule_dir`, of the occurrence waiting to be published
+    next: String,
+    // date of this occurrence
+    date: String,
+}
+
+fn read_recurrences(file: &Path) -> Result<Vec<Recurrence>> {
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&std::fs::read_to_string(file)?)
+        .with_context(|| format!("Failed to parse `{}`", file.to_string_lossy()))
+}
+
+fn write_recurrences(file: &Path, recurrences: &[Recurrence]) -> Result<()> {
+    std::fs::write(file, serde_json::to_string_pretty(recurrences)?)
+        .with_context(|| format!("Failed to write `{}`", file.to_string_lossy()))
+}
+
+// Write the occurrence of `template` at `date` in `schedule_dir`, named after the template and the
+// day, `{date}` being replaced by the day in its content. Returns the file name.
+fn instantiate(template: &Path, date: &DateTime<FixedOffset>, cfg: &SiteConfig) -> Result<String> {
+    let content = std::fs::read_to_string(template)
+        .with_context(|| format!("Failed to read template `{}`", template.to_string_lossy()))?;
+    let day = date.format("%Y-%m-%d").to_string();
+    let content = content.replace(DATE_PLACEHOLDER, &day);
+    let content = set_front_field(&content, "date", &cfg.front_date(date))?;
+    let content = scheduled_content(&content, cfg)?;
+
+    let stem = template.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = template
+        .extension()
+        .unwrap_or("md".as_ref())
+        .to_string_lossy();
+    let filename = format!("{stem}-{day}.{ext}");
+    let dest = cfg.schedule_dir.join(&filename);
+    if dest.exists() {
+        bail!("file {} already exists.", dest.to_string_lossy());
+    }
+    std::fs::write(&dest, content)?;
+    Ok(filename)
+}
+
+// Schedule an occurrence of `template` at `date`, then every `every` after each publication.
+// Returns what was scheduled, to be shown to the user.
+pub fn schedule_recurring(
+    date: &DateTime<FixedOffset>,
+    every: &str,
+    template: &Path,
+    cfg: &SiteConfig,
+) -> Result<String> {
+    schedule_recurring_in(Path::new(REPEAT_FILE), date, every, template, cfg)
+}
+
+fn schedule_recurring_in(
+    file: &Path,
+    date: &DateTime<FixedOffset>,
+    every: &str,
+    template: &Path,
+    cfg: &SiteConfig,
+) -> Result<String> {
+    parse_delay(every)?;
+    if !template.is_file() || !cfg.is_markdown(template) {
+        bail!(
+            "Template must be a markdown file with one of the `{}` extensions",
+            cfg.markdown_extensions.join("`, `")
+        );
+    }
+    let mut recurrences = read_recurrences(file)?;
+    if recurrences.iter().any(|rec| rec.template == template) {
+        bail!(
+            "`{}` is already repeated, remove it from `{}` first",
+            template.to_string_lossy(),
+            file.to_string_lossy()
+        );
+    }
+
+    let next = instantiate(template, date, cfg)?;
+    let scheduled = format!(
+        "Scheduled `{next}` at {}, then every {every}",
+        describe_date(date, Utc::now())
+    );
+    recurrences.push(Recurrence {
+        template: template.to_path_buf(),
+        every: every.to_string(),
+        next,
+        date: date.to_rfc3339(),
+    });
+    write_recurrences(file, &recurrences)?;
+    Ok(scheduled)
+}
+
+// Schedule the occurrence following `published` if it belongs to a recurrence, skipping the
+// occurrences already in the past
+pub fn rearm(published: &Path, cfg: &SiteConfig) -> Result<()> {
+    rearm_in(Path::new(REPEAT_FILE), published, cfg, Utc::now())
+}
+
+fn rearm_in(file: &Path, published: &Path, cfg: &SiteConfig, now: DateTime<Utc>) -> Result<()> {
+    let mut recurrences = read_recurrences(file)?;
+    let filename = published.file_name().unwrap_or_default().to_string_lossy();
+    let Some(rec) = recurrences.iter_mut().find(|rec| rec.next == filename) else {
+        return Ok(());
+    };
+
+    let every = parse_delay(&rec.every)?;
+    let mut date = DateTime::parse_from_rfc3339(&rec.date)? + every;
+    while date <= now {
+        date += every;
+    }
+    rec.next = instantiate(&rec.template, &date, cfg)?;
+    rec.date = date.to_rfc3339();
+    info!(
+        "Next occurrence of `{}` scheduled at {}",
+        rec.template.to_string_lossy(),
+        format_date(&date)
+    );
+    write_recurrences(file, &recurrences)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::{read_recurrences, rearm_in, schedule_recurring_in};
+
+    #[test]
+    fn test_repeat() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            schedule_dir: root.join("scheduled"),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&cfg.schedule_dir).unwrap();
+        let file = root.join("repeat.json");
+        let template = root.join("roundup.md");
+        std::fs::write(&template, "+++\ntitle = \"Roundup of {date}\"\n+++\nNews\n").unwrap();
+
+        let date = DateTime::parse_from_rfc3339("2024-06-03T09:00:00+00:00").unwrap();
+        schedule_recurring_in(&file, &date, "1 week", &template, &cfg).unwrap();
+        let first = cfg.schedule_dir.join("roundup-2024-06-03.md");
+        assert_eq!(
+            std::fs::read_to_string(&first).unwrap(),
+            "+++\ntitle = \"Roundup of 2024-06-03\"\ndate = 2024-06-03T09:00:00+00:00\n+++\nNews\n"
+        );
+        // only one recurrence per template
+        assert!(schedule_recurring_in(&file, &date, "1 week", &template, &cfg).is_err());
+
+        // published late: the missed occurrence is skipped
+        std::fs::remove_file(&first).unwrap();
+        let now = "2024-06-11T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        rearm_in(&file, &first, &cfg, now).unwrap();
+        assert!(cfg.schedule_dir.join("roundup-2024-06-17.md").is_file());
+        let recurrences = read_recurrences(&file).unwrap();
+        assert_eq!(recurrences[0].next, "roundup-2024-06-17.md");
+        assert_eq!(recurrences[0].date, "2024-06-17T09:00:00+00:00");
+
+        // not an occurrence
+        rearm_in(&file, &root.join("other.md"), &cfg, now).unwrap();
+        assert_eq!(read_recurrences(&file).unwrap(), recurrences);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::Duration;
+use slug::slugify;
+
+use crate::{
+    config::SiteConfig,
+    post::{frontmatter, set_front_field},
+};
+
+const CONTENT_DIR: &str = "content";
+
+// Rename the posts after the slug of their title. Directories are searched for posts starting
+// with `-`, only keeping the ones modified in the last `since` if set. With `fix_links`, the Zola internal
+// links (`@/…`) to the renamed posts are updated in all the content. With `keep_urls`, the old
+// URL of a renamed post is added to its `aliases`. With `dry_run`, the renames are only printed.
+pub fn reslug_posts(
+    paths: &[PathBuf],
+    fix_links: bool,
+    keep_urls: bool,
+    since: Option<Duration>,
+    dry_run: bool,
+    cfg: &SiteConfig,
+) -> Result<()> {
+    let since = since
+        .map(|since| -> Result<SystemTime> {
+            SystemTime::now()
+                .checked_sub(since.to_std()?)
+                .context("`since` is too far in the past")
+        })
+        .transpose()?;
+    let posts = collect_posts(paths, since, cfg)?;
+    if dry_run {
+        preview_reslug(&posts, cfg);
+        return Ok(());
+    }
+    for post in &posts {
+        let Some(new_path) = reslug_post(post, cfg)? else {
+            println!("`{}` already has the right name", post.to_string_lossy());
+            continue;
+        };
+        println!(
+            "Renamed `{}` to `{}`",
+            post.to_string_lossy(),
+            new_path.to_string_lossy()
+        );
+        if fix_links {
+            fix_internal_links(post, &new_path, cfg)?;
+        }
+        if keep_urls {
+            keep_url(post, &new_path)?;
+        }
+    }
+    Ok(())
+}
+
+// The files of `paths` as is, and the posts starting with `-` found in its directories, modified
+// after `since`
+fn collect_posts(
+    paths: &[PathBuf],
+    since: Option<SystemTime>,
+    cfg: &SiteConfig,
+) -> Result<Vec<PathBuf>> {
+    let mut posts = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            posts.push(path.clone());
+            continue;
+        }
+        for file in markdown_files(path, cfg)? {
+            if !cfg.is_post(&file) || !is_pending_rename(&file) {
+                continue;
+            }
+            if let Some(since) = since {
+                if std::fs::metadata(&file)?.modified()? < since {
+                    continue;
+                }
+            }
+            posts.push(file);
+        }
+    }
+    Ok(posts)
+}
+
+// Imported files waiting for their name are prefixed by `-`
+fn is_pending_rename(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('-'))
+}
+
+// Print what `reslug_posts` would do, going on after the posts that can't be renamed
+fn preview_reslug(posts: &[PathBuf], cfg: &SiteConfig) {
+    let mut nb_errors = 0;
+    for post in posts {
+        match slugged_path(post, cfg) {
+            Ok(Some(new_path)) => println!(
+                "`{}` -> `{}`",
+                post.to_string_lossy(),
+                new_path.to_string_lossy()
+            ),
+            Ok(None) => {}
+            Err(err) => {
+                println!("Error: {err:#}");
+                nb_errors += 1;
+            }
+        }
+    }
+    println!("Dry run: nothing renamed, {nb_errors} post(s) couldn't be renamed");
+}
+
+// Rename `post` after the slug of its title, returns the new path if it changed
+fn reslug_post(post: &Path, cfg: &SiteConfig) -> Result<Option<PathBuf>> {
+    let Some(new_path) = slugged_path(post, cfg)? else {
+        return Ok(None);
+    };
+    std::fs::rename(post, &new_path)?;
+    Ok(Some(new_path))
+}
+
+// Path of `post` named after the slug of its title, if different from the current one
+fn slugged_path(post: &Path, cfg: &SiteConfig) -> Result<Option<PathBuf>> {
+    if !cfg.is_post(post) || !post.is_file() {
+        bail!("`{}` is not a post", post.to_string_lossy());
+    }
+    let content = std::fs::read_to_string(post)?;
+    let front = toml::from_str::<toml::Table>(&frontmatter(&content)?)
+        .with_context(|| format!("Invalid frontmatter in `{}`", post.to_string_lossy()))?;
+    let Some(title) = front.get("title").and_then(|title| title.as_str()) else {
+        bail!("No `title` in `{}`", post.to_string_lossy());
+    };
+
+    let ext = post.extension().unwrap_or_default().to_string_lossy();
+    let new_path = post.with_file_name(format!("{}.{ext}", slugify(title)));
+    if new_path == post {
+        return Ok(None);
+    }
+    if new_path.exists() {
+        bail!("file `{}` already exists.", new_path.to_string_lossy());
+    }
+    Ok(Some(new_path))
+}
+
+// `@/…` path of a post, as used by Zola internal links
+fn internal_link(path: &Path) -> Result<String> {
+    let content_dir = Path::new(CONTENT_DIR).canonicalize()?;
+    let path = path
+        .parent()
+        .unwrap_or(Path::new(""))
+        .canonicalize()?
+        .join(path.file_name().unwrap_or_default());
+    let rel = path
+        .strip_prefix(&content_dir)
+        .with_context(|| format!("`{}` is not in `{CONTENT_DIR}`", path.to_string_lossy()))?;
+    let rel: Vec<_> = rel.iter().map(|part| part.to_string_lossy()).collect();
+    Ok(format!("@/{}", rel.join("/")))
+}
+
+// URL Zola gives to the post of an internal link, `@/posts/name.md` being at `/posts/name/`
+fn link_url(link: &str) -> String {
+    let path = link.trim_start_matches("@/");
+    let path = path
+        .rsplit_once('.')
+        .map(|(path, _ext)| path)
+        .unwrap_or(path);
+    format!("/{path}/")
+}
+
+// Add `alias` to the `aliases` of the frontmatter, if not already there
+fn add_alias(content: &str, alias: &str) -> Result<String> {
+    let front = toml::from_str::<toml::Table>(&frontmatter(content)?)?;
+    let mut aliases = match front.get("aliases") {
+        Some(toml::Value::Array(aliases)) => aliases.clone(),
+        Some(_) => bail!("`aliases` is not an array"),
+        None => Vec::new(),
+    };
+    if aliases
+        .iter()
+        .any(|existing| existing.as_str() == Some(alias))
+    {
+        return Ok(content.to_string());
+    }
+    aliases.push(toml::Value::from(alias));
+    set_front_field(content, "aliases", &toml::Value::Array(aliases).to_string())
+}
+
+// Keep the URL of the post renamed from `old_path` working with an alias, unless it is pinned by
+// `path` or `slug`
+fn keep_url(old_path: &Path, new_path: &Path) -> Result<()> {
+    let content = std::fs::read_to_string(new_path)?;
+    let front = toml::from_str::<toml::Table>(&frontmatter(&content)?)?;
+    if let Some(key) = ["path", "slug"]
+        .into_iter()
+        .find(|key| front.contains_key(*key))
+    {
+        println!(
+            "URL of `{}` is kept by its `{key}`",
+            new_path.to_string_lossy()
+        );
+        return Ok(());
+    }
+    let old_url = link_url(&internal_link(old_path)?);
+    std::fs::write(new_path, add_alias(&content, &old_url)?)?;
+    println!(
+        "Added alias `{old_url}` to `{}`",
+        new_path.to_string_lossy()
+    );
+    Ok(())
+}
+
+fn fix_internal_links(old_path: &Path, new_path: &Path, cfg: &SiteConfig) -> Result<()> {
+    let old_link = internal_link(old_path)?;
+    let new_link = internal_link(new_path)?;
+    for file in markdown_files(Path::new(CONTENT_DIR), cfg)? {
+        let content = std::fs::read_to_string(&file)?;
+        if content.contains(&old_link) {
+            std::fs::write(&file, content.replace(&old_link, &new_link))?;
+            println!(
+                "Updated links to `{old_link}` in `{}`",
+                file.to_string_lossy()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn markdown_files(dir: &Path, cfg: &SiteConfig) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.append(&mut markdown_files(&path, cfg)?);
+        } else if cfg.is_markdown(&path) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::{add_alias, collect_posts, link_url};
+
+    #[test]
+    fn test_keep_url_alias() {
+        assert_eq!(link_url("@/posts/old-name.md"), "/posts/old-name/");
+        assert_eq!(link_url("@/old-name.md"), "/old-name/");
+
+        let content = "+++\ntitle = \"New name\"\n[extra]\nkey = 1\n+++\nBody\n";
+        let content = add_alias(content, "/posts/old-name/").unwrap();
+        assert_eq!(
+            content,
+            "+++\ntitle = \"New name\"\naliases = [\"/posts/old-name/\"]\n[extra]\nkey = 1\n+++\nBody\n"
+        );
+        // existing aliases are kept, and not duplicated
+        let content = add_alias(&content, "/posts/older-name/").unwrap();
+        let content = add_alias(&content, "/posts/old-name/").unwrap();
+        assert!(content.contains("aliases = [\"/posts/old-name/\", \"/posts/older-name/\"]\n"));
+    }
+
+    #[test]
+    fn test_collect_posts_since() {
+        let dir = test_dir();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        for name in [
+            "-old.md",
+            "sub/-new.md",
+            "named.md",
+            "_index.md",
+            "-notes.txt",
+        ] {
+            std::fs::write(root.join(name), "+++\ntitle = \"Post\"\n+++\n").unwrap();
+        }
+        let now = SystemTime::now();
+        std::fs::File::options()
+            .write(true)
+            .open(root.join("-old.md"))
+            .unwrap()
+            .set_modified(now - Duration::from_secs(3 * 86400))
+            .unwrap();
+
+        let cfg = SiteConfig::default();
+        let dirs = [root.to_path_buf()];
+        let mut all = collect_posts(&dirs, None, &cfg).unwrap();
+        all.sort();
+        assert_eq!(all, [root.join("-old.md"), root.join("sub/-new.md")]);
+
+        let since = now - Duration::from_secs(86400);
+        let recent = collect_posts(&dirs, Some(since), &cfg).unwrap();
+        assert_eq!(recent, [root.join("sub/-new.md")]);
+
+        // files given explicitly are not filtered
+        let explicit = [root.join("-old.md"), root.join("named.md")];
+        assert_eq!(
+            collect_posts(&explicit, Some(since), &cfg).unwrap(),
+            explicit
+        );
+    }
+}
@@ -1,23 +1,35 @@
 use std::{
-    path::{Path, PathBuf},
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
 use anyhow::{bail, Context, Result};
-use chrono::{DateTime, FixedOffset, Utc};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
 use lazy_static::lazy_static;
+use regex::Regex;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tracing::{debug, error, info, warn};
 
 use crate::{
     config::SiteConfig,
+    describe_date,
+    events::Event,
     format_date,
-    post::modify_front,
-    publish::{does_same_title_exist, publish_post},
-    watcher::{SchedulerEvent, SiteWatcher},
+    post::{
+        after_dependency, check_required_fields, dest_filename, extract_date, is_draft,
+        is_section_index, modify_front, set_extra_field, set_front_field, weight, AFTER_DELAY_KEY,
+        AFTER_KEY,
+    },
+    publish::{does_same_title_exist, publish_post, PublishOptions, PublishSource},
+    repeat,
+    status::write_status,
+    watcher::{add_scheduled, SchedulerEvent, SiteWatcher},
 };
 
 struct Scheduled {
+    // date the pending timer is set for
+    date: DateTime<Utc>,
     // here, Option is used as a cell for a type that have no Default impl, so we can use `take()`
     cancel_tx: Option<tokio::sync::oneshot::Sender<()>>,
 }
@@ -28,8 +40,9 @@ impl Scheduled {
         cfg: &SiteConfig,
         tx_scheduler: UnboundedSender<SchedulerEvent>,
     ) -> Result<Self, ()> {
-        if let Some(cancel_tx) = schedule_next(watcher, cfg, tx_scheduler).await {
+        if let Some((date, cancel_tx)) = schedule_next(watcher, cfg, tx_scheduler).await {
             Ok(Self {
+                date,
                 cancel_tx: Some(cancel_tx),
             })
         } else {
@@ -50,11 +63,332 @@ impl Drop for Scheduled {
     }
 }
 
+// directory of the website where the drafts are copied before being moved by `schedule`
+pub const BACKUP_DIR: &str = ".emile-backups";
+
 lazy_static! {
     static ref SCHEDULED: Arc<Mutex<Option<Scheduled>>> = Arc::new(Mutex::new(None));
 }
 
-pub fn schedule_post(date: &DateTime<FixedOffset>, post: &Path, cfg: &SiteConfig) -> Result<()> {
+// Schedule every post, a directory meaning all the markdown files in it. A failure doesn't stop
+// the scheduling of the other posts. Returns the result of `schedule_post` for each post.
+pub fn schedule_posts(
+    date: &DateTime<FixedOffset>,
+    posts: &[PathBuf],
+    cfg: &SiteConfig,
+    now_ok: bool,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    schedule_each(posts, cfg, |post| schedule_post(date, post, cfg, now_ok))
+}
+
+// Same as `schedule_posts`, each post being published `delay` after the post whose slug is `after`
+pub fn schedule_posts_after(
+    after: &str,
+    delay: &str,
+    posts: &[PathBuf],
+    cfg: &SiteConfig,
+    now_ok: bool,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    schedule_each(posts, cfg, |post| {
+        schedule_post_after(after, delay, post, cfg, now_ok)
+    })
+}
+
+// Schedule an occurrence of each template at `date`, the next one being scheduled `every` after
+// each publication
+pub fn schedule_posts_repeat(
+    date: &DateTime<FixedOffset>,
+    every: &str,
+    templates: &[PathBuf],
+    cfg: &SiteConfig,
+    now_ok: bool,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    if !now_ok {
+        check_lead_time(date, cfg)?;
+    }
+    schedule_each(templates, cfg, |template| {
+        repeat::schedule_recurring(date, every, template, cfg)
+    })
+}
+
+fn schedule_each(
+    posts: &[PathBuf],
+    cfg: &SiteConfig,
+    schedule: impl Fn(&Path) -> Result<String>,
+) -> Result<Vec<(PathBuf, Result<String>)>> {
+    let mut to_schedule = Vec::new();
+    for post in posts {
+        if post.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(post)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && cfg.is_post(path))
+                .collect();
+            files.sort();
+            to_schedule.append(&mut files);
+        } else {
+            to_schedule.push(post.clone());
+        }
+    }
+
+    Ok(to_schedule
+        .into_iter()
+        .map(|post| {
+            let res = schedule(&post);
+            (post, res)
+        })
+        .collect())
+}
+
+// Whether `path`, relative to `content`, matches one of Zola's `ignored_content` globs
+fn is_ignored(path: &Path, ignored_content: &[String]) -> bool {
+    let path = path.to_string_lossy().replace('\\', "/");
+    ignored_content.iter().any(|glob| {
+        let pattern = regex::escape(glob)
+            .replace(r"\*\*", ".*")
+            .replace(r"\*", ".*")
+            .replace(r"\?", ".");
+        Regex::new(&format!("^{pattern}$"))
+            .map(|reg| reg.is_match(&path))
+            .unwrap_or(false)
+    })
+}
+
+// A plain `zola build` publishes the scheduled posts before their date if `schedule_dir` is in
+// `content` and they are neither drafts nor in Zola's `ignored_content`: warn about them
+pub fn warn_visible_scheduled(cfg: &SiteConfig) {
+    let dir: PathBuf = cfg
+        .schedule_dir
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect();
+    let dir = std::env::current_dir()
+        .ok()
+        .and_then(|current_dir| dir.strip_prefix(current_dir).ok().map(Path::to_path_buf))
+        .unwrap_or(dir);
+    let Ok(rel_dir) = dir.strip_prefix("content") else {
+        return;
+    };
+
+    let ignored_content: Vec<String> = std::fs::read_to_string("config.toml")
+        .ok()
+        .and_then(|zola_cfg| toml::from_str::<toml::Table>(&zola_cfg).ok())
+        .and_then(|zola_cfg| zola_cfg.get("ignored_content")?.clone().try_into().ok())
+        .unwrap_or_default();
+    let Ok(entries) = std::fs::read_dir(&cfg.schedule_dir) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        if !cfg.is_post(&path) || is_ignored(&rel_dir.join(file_name), &ignored_content) {
+            continue;
+        }
+        let is_draft = std::fs::read_to_string(&path)
+            .map(|content| is_draft(&content))
+            .unwrap_or(true);
+        if !is_draft {
+            warn!(
+                "`{}` is not a draft nor in Zola's `ignored_content`, `zola build` would publish it \
+                 before its date (see `schedule_as_draft`)",
+                path.to_string_lossy()
+            );
+        }
+    }
+}
+
+// Fail if `date` is in the past or sooner than `min_schedule_lead_secs`, as the post would be
+// published right away
+fn check_lead_time(date: &DateTime<FixedOffset>, cfg: &SiteConfig) -> Result<()> {
+    let lead = (*date - Utc::now().fixed_offset()).num_seconds();
+    if lead < 0 {
+        bail!(
+            "`{}` is in the past, use `--now-ok` to publish right away",
+            format_date(date)
+        );
+    }
+    if lead < cfg.min_schedule_lead_secs as i64 {
+        bail!(
+            "`{}` is less than `min_schedule_lead_secs` ({}s) from now, use `--now-ok` to \
+             schedule it anyway",
+            format_date(date),
+            cfg.min_schedule_lead_secs
+        );
+    }
+    Ok(())
+}
+
+// Move `post` to the schedule directory with `date`, returns what was done, to be shown to the user
+pub fn schedule_post(
+    date: &DateTime<FixedOffset>,
+    post: &Path,
+    cfg: &SiteConfig,
+    now_ok: bool,
+) -> Result<String> {
+    if !now_ok {
+        check_lead_time(date, cfg)?;
+    }
+    check_schedulable(post, cfg)?;
+
+    let content = modify_front(post, |cur_line: &str| {
+        let modified = if cur_line.starts_with("date = ") {
+            // modify date
+            format!("date = {}\n", cfg.front_date(date))
+        } else {
+            // don’t modify
+            format!("{cur_line}\n")
+        };
+        Ok(modified)
+    })?;
+
+    let filename = move_to_schedule_dir(post, &content, cfg)?;
+    Ok(format!(
+        "Moved `{}` to scheduled folder with date {}",
+        filename.to_string_lossy(),
+        describe_date(date, Utc::now())
+    ))
+}
+
+// Schedule `post` to be published `delay` after the post whose slug is `after`. If `after` is
+// not published yet, `post` waits in the schedule directory until it is.
+pub fn schedule_post_after(
+    after: &str,
+    delay: &str,
+    post: &Path,
+    cfg: &SiteConfig,
+    now_ok: bool,
+) -> Result<String> {
+    let duration = parse_delay(delay)?;
+    let published = cfg
+        .publish_dirs()
+        .flat_map(|dir| {
+            cfg.markdown_extensions
+                .iter()
+                .map(move |ext| dir.join(format!("{after}.{ext}")))
+        })
+        .find(|published| published.is_file());
+    if let Some(published) = published {
+        let date = extract_date(&published, cfg)? + duration;
+        return schedule_post(&date, post, cfg, now_ok);
+    }
+    check_schedulable(post, cfg)?;
+
+    let content = std::fs::read_to_string(post)?;
+    let content = set_extra_field(
+        &content,
+        AFTER_KEY,
+        Some(&toml::Value::from(after).to_string()),
+    )?;
+    let content = set_extra_field(
+        &content,
+        AFTER_DELAY_KEY,
+        Some(&toml::Value::from(delay).to_string()),
+    )?;
+
+    let filename = move_to_schedule_dir(post, &content, cfg)?;
+    Ok(format!(
+        "Moved `{}` to scheduled folder, to be published {delay} after `{after}`",
+        filename.to_string_lossy()
+    ))
+}
+
+// Schedule `post` at the `date` already in its frontmatter
+pub fn queue_post(post: &Path, cfg: &SiteConfig) -> Result<String> {
+    let date = extract_date(post, cfg).with_context(|| {
+        format!(
+            "Can't queue `{}`, set a future `date` in its frontmatter or use `schedule`",
+            post.to_string_lossy()
+        )
+    })?;
+    if date <= Utc::now() {
+        bail!(
+            "The `date` of `{}` ({}) is in the past, set a future one or use `schedule`",
+            post.to_string_lossy(),
+            format_date(&date)
+        );
+    }
+    schedule_post(&date, post, cfg, false)
+}
+
+// Move the scheduled post `slug` back to `drafts_creation_dir` as a draft, returns its new path.
+// A running `watch` sees it leave `schedule_dir` and unschedules it.
+pub fn cancel_scheduled(slug: &str, cfg: &SiteConfig) -> Result<PathBuf> {
+    let Some(post) = std::fs::read_dir(&cfg.schedule_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| cfg.is_post(path) && path.file_stem() == Some(slug.as_ref()))
+    else {
+        bail!(
+            "No post `{slug}` in `{}`",
+            cfg.schedule_dir.to_string_lossy()
+        );
+    };
+    let content = std::fs::read_to_string(&post)?;
+    let content = set_front_field(&content, "draft", "true")?;
+    let dest = cfg
+        .drafts_creation_dir
+        .join(post.file_name().expect("file with no name"));
+    if dest.exists() {
+        bail!("file {} already exists.", dest.to_string_lossy());
+    }
+    std::fs::create_dir_all(&cfg.drafts_creation_dir)?;
+    std::fs::write(&dest, content)?;
+    std::fs::remove_file(&post)?;
+    Ok(dest)
+}
+
+// Parse a delay like "+2 days", in minutes, hours, days or weeks
+pub fn parse_delay(delay: &str) -> Result<Duration> {
+    let reg = regex::Regex::new(r"^\+?\s*(\d+)\s*(minute|hour|day|week)s?$").unwrap();
+    let Some(caps) = reg.captures(delay.trim()) else {
+        bail!("Invalid delay `{delay}`, expected something like \"+2 days\"");
+    };
+    let amount: i64 = caps[1].parse()?;
+    let duration = match &caps[2] {
+        "minute" => Duration::try_minutes(amount),
+        "hour" => Duration::try_hours(amount),
+        "day" => Duration::try_days(amount),
+        _ => Duration::try_weeks(amount),
+    };
+    duration.with_context(|| format!("Delay `{delay}` is too big"))
+}
+
+// Schedule the posts waiting for the post `slug`, which has just been published at `date`
+pub fn schedule_dependents(
+    slug: &str,
+    date: &DateTime<FixedOffset>,
+    cfg: &SiteConfig,
+) -> Result<()> {
+    if !cfg.schedule_dir.is_dir() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(&cfg.schedule_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || !cfg.is_post(&path) {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path)?;
+        let Some((after, delay)) = after_dependency(&content) else {
+            continue;
+        };
+        if after != slug {
+            continue;
+        }
+
+        let pub_date = *date + parse_delay(&delay)?;
+        let content = set_front_field(&content, "date", &cfg.front_date(&pub_date))?;
+        let content = set_extra_field(&content, AFTER_KEY, None)?;
+        let content = set_extra_field(&content, AFTER_DELAY_KEY, None)?;
+        std::fs::write(&path, content)?;
+        info!(
+            "`{}` scheduled at {} after the publication of `{slug}`",
+            path.to_string_lossy(),
+            format_date(&pub_date)
+        );
+    }
+    Ok(())
+}
+
+fn check_schedulable(post: &Path, cfg: &SiteConfig) -> Result<()> {
     if !post
         .canonicalize()
         .with_context(|| format!("canonicalize() of `{}` failed", post.to_string_lossy()))?
@@ -71,32 +405,41 @@ pub fn schedule_post(date: &DateTime<FixedOffset>, post: &Path, cfg: &SiteConfig
         );
     }
 
-    if !post
-        .extension()
-        .map(|ext| ext.to_string_lossy().to_lowercase() == "md")
-        .unwrap_or(false)
-        || !post.is_file()
-    {
-        bail!("Post must be a markdown file with `md` extensions");
+    if !cfg.is_markdown(post) || !post.is_file() {
+        bail!(
+            "Post must be a markdown file with one of the `{}` extensions",
+            cfg.markdown_extensions.join("`, `")
+        );
+    }
+
+    if is_section_index(post) {
+        bail!(
+            "`{}` is the index of a section, not a post",
+            post.to_string_lossy()
+        );
     }
 
     if !post.exists() {
         bail!("Post `{}` not found", post.to_string_lossy());
     }
+    check_required_fields(&std::fs::read_to_string(post)?, cfg)
+        .with_context(|| format!("`{}` can't be scheduled", post.to_string_lossy()))
+}
 
-    let content = modify_front(post, |cur_line: &str| {
-        let modified = if cur_line.starts_with("date = ") {
-            // modify date
-            format!("date = {}\n", format_date(&date))
-        } else {
-            // don’t modify
-            format!("{cur_line}\n")
-        };
-        Ok(modified)
-    })?;
+// Content of a post moved to `schedule_dir`, set as a draft if `schedule_as_draft`
+pub fn scheduled_content(content: &str, cfg: &SiteConfig) -> Result<String> {
+    if cfg.schedule_as_draft {
+        set_front_field(content, "draft", "true")
+    } else {
+        Ok(content.to_string())
+    }
+}
 
-    let filename = post.file_name().expect("Post must be a file");
-    let dest = cfg.schedule_dir.join(filename);
+// Write `content` in the schedule directory and remove `post`, returns the new file name
+fn move_to_schedule_dir(post: &Path, content: &str, cfg: &SiteConfig) -> Result<OsString> {
+    let content = scheduled_content(content, cfg)?;
+    let filename = dest_filename(post, &content);
+    let dest = cfg.schedule_dir.join(&filename);
     if dest.exists() {
         bail!("file {} already exists.", dest.to_string_lossy());
     }
@@ -106,17 +449,41 @@ pub fn schedule_post(date: &DateTime<FixedOffset>, post: &Path, cfg: &SiteConfig
     {
         bail!(
             "Warning: a post with a the same title exists: `{}`",
-            similar_file.file_name().to_string_lossy()
+            similar_file.to_string_lossy()
         );
     }
 
-    std::fs::write(&dest, &content)?;
-    std::fs::remove_file(&post)?;
-    println!(
-        "Moved `{}` to scheduled folder with date {}",
-        filename.to_string_lossy(),
-        format_date(&date)
-    );
+    if cfg.schedule_backups > 0 {
+        backup_draft(
+            post,
+            Path::new(BACKUP_DIR),
+            cfg.schedule_backups,
+            Utc::now(),
+        )
+        .with_context(|| format!("Failed to back up `{}`", post.to_string_lossy()))?;
+    }
+    std::fs::write(&dest, content)?;
+    std::fs::remove_file(post)?;
+    Ok(filename)
+}
+
+// Copy `post` in `dir`, prefixed by the time, and remove the oldest copies beyond `keep`
+fn backup_draft(post: &Path, dir: &Path, keep: usize, now: DateTime<Utc>) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let name = post.file_name().unwrap_or_default().to_string_lossy();
+    let backup = dir.join(format!("{}-{name}", now.format("%Y%m%dT%H%M%S%.3fZ")));
+    std::fs::copy(post, &backup)?;
+
+    // the time prefix sorts them from the oldest
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    backups.sort();
+    let nb_old = backups.len().saturating_sub(keep);
+    for old in &backups[..nb_old] {
+        std::fs::remove_file(old)?;
+    }
     Ok(())
 }
 
@@ -124,7 +491,7 @@ async fn schedule_next(
     watcher: Arc<SiteWatcher>,
     cfg: &SiteConfig,
     tx_scheduler: UnboundedSender<SchedulerEvent>,
-) -> Option<tokio::sync::oneshot::Sender<()>> {
+) -> Option<(DateTime<Utc>, tokio::sync::oneshot::Sender<()>)> {
     parse_scheduled(watcher.clone(), cfg, tx_scheduler)
         .await
         .map(
@@ -139,24 +506,93 @@ async fn schedule_next(
                     for p in path_to_remove {
                         index.remove(&p);
                     }
-                    res.tx
+                    (res.date, res.tx)
                 }
-                _ => res.tx,
+                _ => (res.date, res.tx),
             },
         )
 }
 
 struct ParseResult {
+    date: DateTime<Utc>,
     tx: tokio::sync::oneshot::Sender<()>,
     date_to_remove: Vec<DateTime<Utc>>,
     path_to_remove: Vec<PathBuf>,
 }
 
+// Posts due for longer than `past_schedule_grace_secs` are left in `schedule_dir` instead of being
+// published by surprise, they were likely scheduled in the past by mistake
+fn skip_overdue(watcher: &SiteWatcher, cfg: &SiteConfig, now: DateTime<Utc>) {
+    let Some(grace) = cfg.past_schedule_grace_secs else {
+        return;
+    };
+    let limit = now - Duration::seconds(grace as i64);
+    match (watcher.scheduled.lock(), watcher.index.lock()) {
+        (Ok(mut scheduled), Ok(mut index)) => {
+            let overdue: Vec<_> = scheduled.range(..limit).map(|(date, _)| *date).collect();
+            for date in overdue {
+                for path in scheduled.remove(&date).unwrap_or_default() {
+                    warn!(
+                        "`{}` is overdue since {date}, skipping: left for a manual release",
+                        path.to_string_lossy()
+                    );
+                    index.remove(&path);
+                }
+            }
+        }
+        _ => error!("Error getting lock on SiteWatcher"),
+    }
+}
+
+// Past-due posts beyond `catchup_limit` are not published in a burst: they are rescheduled
+// `catchup_spacing_secs` apart, or left for a manual release
+fn limit_catchup(watcher: &SiteWatcher, cfg: &SiteConfig, now: DateTime<Utc>) {
+    let Some(limit) = cfg.catchup_limit else {
+        return;
+    };
+    match (watcher.scheduled.lock(), watcher.index.lock()) {
+        (Ok(mut scheduled), Ok(mut index)) => {
+            let past_due: Vec<_> = scheduled
+                .range(..=now)
+                .flat_map(|(date, paths)| paths.iter().map(|path| (*date, path.clone())))
+                .collect();
+            if past_due.len() <= limit {
+                return;
+            }
+            warn!(
+                "{} posts scheduled in the past, only {limit} are published now",
+                past_due.len()
+            );
+            for (i, (date, path)) in past_due.into_iter().skip(limit).enumerate() {
+                if let Some(paths) = scheduled.get_mut(&date) {
+                    paths.retain(|p| p != &path);
+                    if paths.is_empty() {
+                        scheduled.remove(&date);
+                    }
+                }
+                if cfg.catchup_spacing_secs == 0 {
+                    warn!("`{}` is left for a manual release", path.to_string_lossy());
+                    index.remove(&path);
+                } else {
+                    let delay = cfg.catchup_spacing_secs * (i as u64 + 1);
+                    let new_date = now + Duration::seconds(delay as i64);
+                    info!("Postpone `{}` to {new_date}", path.to_string_lossy());
+                    add_scheduled(&mut scheduled, new_date, path.clone());
+                    index.insert(path, new_date);
+                }
+            }
+        }
+        _ => error!("Error getting lock on SiteWatcher"),
+    }
+}
+
 async fn parse_scheduled(
     watcher: Arc<SiteWatcher>,
     cfg: &SiteConfig,
     tx_scheduler: UnboundedSender<SchedulerEvent>,
 ) -> Option<ParseResult> {
+    skip_overdue(&watcher, cfg, Utc::now());
+    limit_catchup(&watcher, cfg, Utc::now());
     let mut date_to_remove = Vec::new();
     let mut path_to_remove = Vec::new();
     let mut path_to_publish = Vec::new();
@@ -172,8 +608,8 @@ async fn parse_scheduled(
                     date_to_remove.push(date);
                     for path in paths {
                         path_to_remove.push((*path).clone());
-                        path_to_publish.push((*path).clone());
                     }
+                    path_to_publish.extend(publish_order(paths, cfg));
                 } else {
                     let (tx, rx) = tokio::sync::oneshot::channel();
 
@@ -192,6 +628,7 @@ async fn parse_scheduled(
                     });
 
                     res = Some(ParseResult {
+                        date,
                         tx,
                         date_to_remove,
                         path_to_remove,
@@ -203,16 +640,100 @@ async fn parse_scheduled(
         Err(err) => error!("Error getting lock on SiteWatcher: {:?}", err),
     }
 
-    for path in &path_to_publish {
+    publish_scheduled(&watcher, &path_to_publish, cfg).await;
+    res
+}
+
+// Posts scheduled at the same date are published by increasing `weight`, then the ones without
+// one, each in file name order as they are kept in `scheduled`
+fn publish_order(paths: &[PathBuf], cfg: &SiteConfig) -> Vec<PathBuf> {
+    let mut weighted: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            let weight = std::fs::read_to_string(cfg.schedule_dir.join(path))
+                .ok()
+                .and_then(|content| weight(&content));
+            (weight.is_none(), weight, path.clone())
+        })
+        .collect();
+    weighted.sort_by_key(|(no_weight, weight, _)| (*no_weight, *weight));
+    weighted.into_iter().map(|(_, _, path)| path).collect()
+}
+
+async fn publish_scheduled(watcher: &SiteWatcher, paths: &[PathBuf], cfg: &SiteConfig) {
+    for path in paths {
         let path = &cfg.schedule_dir.join(path);
-        match publish_post(path, cfg).await {
+        if watcher.dry_run {
+            info!("Dry run: would publish `{}`", path.to_string_lossy());
+            continue;
+        }
+        let opts = PublishOptions {
+            source: PublishSource::Scheduled,
+            ..Default::default()
+        };
+        match publish_post(path, cfg, &opts).await {
             Ok(dest) => {
-                info!("Scheduled post published: {}", dest)
+                info!("Scheduled post published: {}", dest);
+                watcher.emit(Event::PostPublished {
+                    path: path.to_string_lossy().to_string(),
+                    dest,
+                });
+                if let Err(err) = repeat::rearm(path, cfg) {
+                    error!(
+                        "Error scheduling the next occurrence of `{}`: {err:#}",
+                        path.to_string_lossy()
+                    );
+                }
             }
             Err(err) => error!("Error while publishing: {}", err),
         }
     }
-    res
+}
+
+// Cancel the pending timer and start one for the next scheduled post. The cancellation happens
+// first so the previous timer can't fire for an outdated date, even if nothing is left to schedule.
+async fn reschedule(
+    watcher: Arc<SiteWatcher>,
+    cfg: &SiteConfig,
+    tx_scheduler: UnboundedSender<SchedulerEvent>,
+) {
+    match SCHEDULED.lock() {
+        Ok(mut locked_scheduled) => {
+            locked_scheduled.take();
+        }
+        Err(e) => error!("Failed to get lock on SCHEDULED: {:?}", e),
+    }
+    if let Ok(new_scheduled) = Scheduled::new(watcher, cfg, tx_scheduler).await {
+        match SCHEDULED.lock() {
+            Ok(mut locked_scheduled) => {
+                locked_scheduled.replace(new_scheduled);
+            }
+            Err(e) => error!("Failed to get lock on SCHEDULED: {:?}", e),
+        }
+    }
+}
+
+// Consume the pending timer if it is set for `date`. A timer firing right before being cancelled
+// sends an outdated date, which must not cancel the timer that replaced it.
+fn take_due(date: DateTime<Utc>) -> bool {
+    match SCHEDULED.lock() {
+        Ok(mut locked_scheduled) => {
+            if locked_scheduled
+                .as_ref()
+                .map(|scheduled| scheduled.date == date)
+                .unwrap_or(false)
+            {
+                locked_scheduled.take();
+                true
+            } else {
+                false
+            }
+        }
+        Err(e) => {
+            error!("Failed to get lock on SCHEDULED: {:?}", e);
+            false
+        }
+    }
 }
 
 pub async fn start_scheduler(
@@ -224,33 +745,21 @@ pub async fn start_scheduler(
     while let Some(e) = rx_scheduler.recv().await {
         match e {
             SchedulerEvent::Changed => {
-                if let Ok(new_scheduled) =
-                    Scheduled::new(watcher.clone(), &cfg, tx_scheduler.clone()).await
-                {
-                    match SCHEDULED.lock() {
-                        Ok(mut locked_scheduled) => {
-                            locked_scheduled.replace(new_scheduled);
-                        }
-                        Err(e) => error!("Failed to get lock on SCHEDULED: {:?}", e),
-                    }
-                }
+                reschedule(watcher.clone(), &cfg, tx_scheduler.clone()).await;
+                watcher.emit_scheduled_changed(&cfg);
             }
             SchedulerEvent::Scheduled(date) => {
-                match SCHEDULED.lock() {
-                    Ok(mut locked_scheduled) => {
-                        locked_scheduled.take();
-                    }
-                    Err(e) => error!("Failed to get lock on SCHEDULED: {:?}", e),
-                }
-                {
+                if !take_due(date) {
+                    debug!("Ignore outdated schedule for date: {}", date);
+                } else {
                     let mut paths_to_publish = Vec::new();
                     match (watcher.scheduled.lock(), watcher.index.lock()) {
                         (Ok(mut scheduled), Ok(mut index)) => match scheduled.remove(&date) {
                             Some(paths) => {
                                 for path in &paths {
                                     index.remove(path);
-                                    paths_to_publish.push(path.clone());
                                 }
+                                paths_to_publish = publish_order(&paths, &cfg);
                             }
                             None => {
                                 warn!("Something was scheduled at this date, but no paths found")
@@ -261,17 +770,312 @@ pub async fn start_scheduler(
                         }
                     }
 
-                    for path in &paths_to_publish {
-                        let path = &cfg.schedule_dir.join(path);
-                        match publish_post(path, &cfg).await {
-                            Ok(dest) => {
-                                info!("Scheduled post published: {}", dest);
-                            }
-                            Err(err) => error!("Error while publishing: {}", err),
-                        }
+                    publish_scheduled(&watcher, &paths_to_publish, &cfg).await;
+                    // arm the timer for the next scheduled post
+                    if let Err(e) = tx_scheduler.send(SchedulerEvent::Changed) {
+                        error!("Error sending ScheduleEvent: {:?}", e)
                     }
                 }
             }
         }
+
+        if let Err(err) = write_status(&watcher, &cfg) {
+            error!("Failed to write status: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+    };
+
+    use chrono::{DateTime, Duration, Utc};
+
+    use crate::{
+        config::SiteConfig,
+        post::{after_dependency, is_draft},
+        test_dir,
+        watcher::{SchedulerEvent, SiteWatcher},
+    };
+
+    use super::{
+        backup_draft, cancel_scheduled, is_ignored, limit_catchup, parse_delay, publish_order,
+        reschedule, schedule_dependents, schedule_post, skip_overdue, take_due,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reschedule_earlier() {
+        let now = Utc::now();
+        let post = PathBuf::from("post.md");
+        let late = now + Duration::seconds(30);
+        let watcher = Arc::new(SiteWatcher {
+            scheduled: Mutex::new(BTreeMap::from([(late, vec![post.clone()])])),
+            index: Mutex::new(BTreeMap::from([(post.clone(), late)])),
+            dry_run: true,
+            events: None,
+        });
+        let cfg = SiteConfig::default();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        reschedule(watcher.clone(), &cfg, tx.clone()).await;
+
+        // the post is moved earlier, as the watcher does when its date is edited
+        let early = now + Duration::seconds(10);
+        {
+            let mut scheduled = watcher.scheduled.lock().unwrap();
+            scheduled.clear();
+            scheduled.insert(early, vec![post.clone()]);
+            watcher.index.lock().unwrap().insert(post, early);
+        }
+        reschedule(watcher.clone(), &cfg, tx).await;
+        assert!(!take_due(late));
+
+        let Some(SchedulerEvent::Scheduled(date)) = rx.recv().await else {
+            panic!("Expected a Scheduled event");
+        };
+        assert_eq!(date, early);
+        assert!(take_due(date));
+
+        // the timer of the previous date never fires
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_limit_catchup() {
+        let now = Utc::now();
+        let posts: Vec<_> = (1..=3)
+            .map(|i| PathBuf::from(format!("post-{i}.md")))
+            .collect();
+        let past = now - Duration::days(1);
+        let watcher = SiteWatcher {
+            scheduled: Mutex::new(BTreeMap::from([(past, posts.clone())])),
+            index: Mutex::new(posts.iter().map(|post| (post.clone(), past)).collect()),
+            dry_run: true,
+            events: None,
+        };
+        let cfg = SiteConfig {
+            catchup_limit: Some(1),
+            catchup_spacing_secs: 60,
+            ..Default::default()
+        };
+        limit_catchup(&watcher, &cfg, now);
+
+        let first = now + Duration::seconds(60);
+        let second = now + Duration::seconds(120);
+        let scheduled = watcher.scheduled.lock().unwrap();
+        assert_eq!(
+            *scheduled,
+            BTreeMap::from([
+                (past, vec![posts[0].clone()]),
+                (first, vec![posts[1].clone()]),
+                (second, vec![posts[2].clone()]),
+            ])
+        );
+        assert_eq!(watcher.index.lock().unwrap()[&posts[2]], second);
+
+        // without spacing, the extra posts are unscheduled
+        let cfg = SiteConfig {
+            catchup_limit: Some(0),
+            catchup_spacing_secs: 0,
+            ..cfg
+        };
+        drop(scheduled);
+        limit_catchup(&watcher, &cfg, now);
+        assert_eq!(watcher.scheduled.lock().unwrap().len(), 2);
+        assert!(!watcher.index.lock().unwrap().contains_key(&posts[0]));
+    }
+
+    #[test]
+    fn test_skip_overdue() {
+        let now = Utc::now();
+        let (stale, recent) = (PathBuf::from("stale.md"), PathBuf::from("recent.md"));
+        let stale_date = now - Duration::days(30);
+        let recent_date = now - Duration::seconds(30);
+        let watcher = SiteWatcher {
+            scheduled: Mutex::new(BTreeMap::from([
+                (stale_date, vec![stale.clone()]),
+                (recent_date, vec![recent.clone()]),
+            ])),
+            index: Mutex::new(BTreeMap::from([
+                (stale.clone(), stale_date),
+                (recent.clone(), recent_date),
+            ])),
+            dry_run: true,
+            events: None,
+        };
+        skip_overdue(&watcher, &SiteConfig::default(), now);
+        assert_eq!(watcher.scheduled.lock().unwrap().len(), 2);
+
+        let cfg = SiteConfig {
+            past_schedule_grace_secs: Some(3600),
+            ..Default::default()
+        };
+        skip_overdue(&watcher, &cfg, now);
+        assert_eq!(
+            *watcher.scheduled.lock().unwrap(),
+            BTreeMap::from([(recent_date, vec![recent.clone()])])
+        );
+        assert!(!watcher.index.lock().unwrap().contains_key(&stale));
+    }
+
+    #[test]
+    fn test_publish_order() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            schedule_dir: root.to_path_buf(),
+            ..Default::default()
+        };
+        for (name, weight) in [
+            ("a-post.md", "weight = 3\n"),
+            ("b-post.md", ""),
+            ("c-post.md", "weight = 1\n"),
+            ("d-post.md", "weight = 1\n"),
+        ] {
+            let content =
+                format!("+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+00:00\n{weight}+++\n");
+            std::fs::write(root.join(name), content).unwrap();
+        }
+
+        let watcher = SiteWatcher::new(&cfg).unwrap();
+        let scheduled = watcher.scheduled.into_inner().unwrap();
+        let paths = scheduled.values().next().unwrap();
+        assert_eq!(
+            publish_order(paths, &cfg),
+            ["c-post.md", "d-post.md", "a-post.md", "b-post.md"].map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let ignored = vec!["drafts/**".to_string(), "*.xlsx".to_string()];
+        assert!(is_ignored(Path::new("drafts/scheduled/post.md"), &ignored));
+        assert!(is_ignored(Path::new("posts/data.xlsx"), &ignored));
+        assert!(!is_ignored(Path::new("scheduled/post.md"), &ignored));
+        assert!(!is_ignored(Path::new("drafts.md"), &ignored));
+    }
+
+    #[test]
+    fn test_parse_delay() {
+        assert_eq!(parse_delay("+2 days").unwrap(), Duration::days(2));
+        assert_eq!(parse_delay("1 hour").unwrap(), Duration::hours(1));
+        assert_eq!(parse_delay("+30minutes").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_delay("+1 week").unwrap(), Duration::weeks(1));
+        assert!(parse_delay("tomorrow").is_err());
+    }
+
+    #[test]
+    fn test_schedule_dependents() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            schedule_dir: root.to_path_buf(),
+            ..Default::default()
+        };
+        let waiting = "+++\ntitle = \"Part 2\"\ndate = 2024-01-01\n[extra]\nemile_after = \"part-1\"\nemile_after_delay = \"+2 days\"\n+++\n";
+        std::fs::write(root.join("part-2.md"), waiting).unwrap();
+        std::fs::write(root.join("other.md"), waiting.replace("part-1", "other-1")).unwrap();
+
+        let date = DateTime::parse_from_rfc3339("2024-06-27T12:00:00+02:00").unwrap();
+        schedule_dependents("part-1", &date, &cfg).unwrap();
+
+        let content = std::fs::read_to_string(root.join("part-2.md")).unwrap();
+        assert_eq!(
+            content,
+            "+++\ntitle = \"Part 2\"\ndate = 2024-06-29T12:00:00+02:00\n[extra]\n+++\n"
+        );
+        let other = std::fs::read_to_string(root.join("other.md")).unwrap();
+        assert!(after_dependency(&other).is_some());
+    }
+
+    #[test]
+    fn test_cancel_scheduled() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            schedule_dir: root.join("scheduled"),
+            drafts_creation_dir: root.join("drafts"),
+            ..Default::default()
+        };
+        std::fs::create_dir_all(&cfg.schedule_dir).unwrap();
+        let content = "+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+00:00\n+++\n";
+        for name in ["a-post.md", "b-post.md"] {
+            std::fs::write(cfg.schedule_dir.join(name), content).unwrap();
+        }
+
+        let dest = cancel_scheduled("a-post", &cfg).unwrap();
+        assert_eq!(dest, cfg.drafts_creation_dir.join("a-post.md"));
+        assert!(!cfg.schedule_dir.join("a-post.md").exists());
+        let cancelled = std::fs::read_to_string(&dest).unwrap();
+        assert!(is_draft(&cancelled));
+        assert!(cancel_scheduled("c-post", &cfg).is_err());
+
+        // a restarted `watch` doesn't schedule it again
+        let watcher = SiteWatcher::new(&cfg).unwrap();
+        let paths: Vec<_> = watcher.index.into_inner().unwrap().into_keys().collect();
+        assert_eq!(paths, [PathBuf::from("b-post.md")]);
+    }
+
+    #[test]
+    fn test_backup_draft() {
+        let dir = test_dir();
+        let root = dir.path();
+        let backups = root.join("backups");
+        let now = "2024-06-27T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for (i, name) in ["a.md", "b.md", "c.md"].into_iter().enumerate() {
+            let post = root.join(name);
+            std::fs::write(&post, name).unwrap();
+            backup_draft(&post, &backups, 2, now + Duration::seconds(i as i64)).unwrap();
+        }
+
+        let mut kept: Vec<_> = std::fs::read_dir(&backups)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        kept.sort();
+        assert_eq!(
+            kept,
+            ["20240627T120001.000Z-b.md", "20240627T120002.000Z-c.md"]
+        );
+        let content = std::fs::read_to_string(backups.join(&kept[1])).unwrap();
+        assert_eq!(content, "c.md");
+    }
+
+    #[test]
+    fn test_schedule_as_draft() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            drafts_creation_dir: root.join("drafts"),
+            schedule_dir: root.join("scheduled"),
+            publish_dest: root.join("posts"),
+            schedule_as_draft: true,
+            ..Default::default()
+        };
+        for dir in [
+            &cfg.drafts_creation_dir,
+            &cfg.schedule_dir,
+            &cfg.publish_dest,
+        ] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let post = cfg.drafts_creation_dir.join("post.md");
+        std::fs::write(
+            &post,
+            "+++\ntitle = \"Post\"\ndate = 2024-01-01\n+++\nHello\n",
+        )
+        .unwrap();
+
+        let date = DateTime::parse_from_rfc3339("2024-06-27T12:00:00+00:00").unwrap();
+        schedule_post(&date, &post, &cfg, true).unwrap();
+        let content = std::fs::read_to_string(cfg.schedule_dir.join("post.md")).unwrap();
+        assert_eq!(
+            content,
+            "+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+00:00\ndraft = true\n+++\nHello\n"
+        );
     }
 }
@@ -1,19 +1,23 @@
 use anyhow::{bail, Ok, Result};
 use chrono::Utc;
 use regex::Regex;
-use reqwest::{StatusCode, Url};
+use reqwest::{Client, StatusCode, Url};
 use serde_derive::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{config::SocialInstance, format_utc_date};
 
-use super::{Lang, StatusContent};
+use super::{send, send_media, server_url, Lang, Media, SocialPost, StatusContent};
+
+// maximum size of a blob accepted by Bluesky
+const BLOB_SIZE_LIMIT: usize = 1_000_000;
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Session {
     access_jwt: String,
     did: String,
+    handle: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -31,10 +35,33 @@ struct Record {
     langs: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     facets: Option<Vec<Facet>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<SelfLabels>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    embed: Option<ImagesEmbed>,
+}
+
+#[derive(Serialize)]
+struct ImagesEmbed {
+    #[serde(rename = "$type")]
+    r#type: &'static str,
+    images: Vec<EmbeddedImage>,
+}
+
+#[derive(Serialize)]
+struct EmbeddedImage {
+    alt: String,
+    // blob reference, as returned by the upload
+    image: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct UploadedBlob {
+    blob: serde_json::Value,
 }
 
 impl Record {
-    fn new(text: String, lang: &Lang) -> Self {
+    fn new(text: String, lang: &Lang, labels: &[String]) -> Self {
         let facets = parse_facets(&text);
         Self {
             r#type: "app.bsky.feed.post",
@@ -46,6 +73,42 @@ impl Record {
             } else {
                 Some(facets)
             },
+            labels: if labels.is_empty() {
+                None
+            } else {
+                Some(SelfLabels::new(labels))
+            },
+            embed: None,
+        }
+    }
+
+    fn add_facets(&mut self, mut facets: Vec<Facet>) {
+        if !facets.is_empty() {
+            self.facets.get_or_insert_with(Vec::new).append(&mut facets);
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SelfLabel {
+    val: String,
+}
+
+#[derive(Serialize)]
+struct SelfLabels {
+    #[serde(rename = "$type")]
+    r#type: &'static str,
+    values: Vec<SelfLabel>,
+}
+
+impl SelfLabels {
+    fn new(labels: &[String]) -> Self {
+        Self {
+            r#type: "com.atproto.label.defs#selfLabels",
+            values: labels
+                .iter()
+                .map(|label| SelfLabel { val: label.clone() })
+                .collect(),
         }
     }
 }
@@ -63,11 +126,11 @@ struct RecordCreation<'a> {
 }
 
 impl<'a> RecordCreation<'a> {
-    fn new(session: &'a Session, text: String, lang: &Lang) -> Self {
+    fn new(session: &'a Session, text: String, lang: &Lang, labels: &[String]) -> Self {
         Self {
             repo: &session.did,
             collection: "app.bsky.feed.post",
-            record: Record::new(text, lang),
+            record: Record::new(text, lang, labels),
         }
     }
 }
@@ -77,6 +140,11 @@ struct Profile {
     handle: String,
 }
 
+#[derive(Deserialize)]
+struct ResolvedHandle {
+    did: String,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Index {
@@ -89,6 +157,7 @@ struct Index {
 enum FeatureType {
     Link(&'static str),
     Hashtag(&'static str),
+    Mention(&'static str),
 }
 
 impl FeatureType {
@@ -99,6 +168,10 @@ impl FeatureType {
     fn hashtag() -> Self {
         FeatureType::Hashtag("app.bsky.richtext.facet#tag")
     }
+
+    fn mention() -> Self {
+        FeatureType::Mention("app.bsky.richtext.facet#mention")
+    }
 }
 
 #[derive(Serialize)]
@@ -106,6 +179,7 @@ impl FeatureType {
 enum FeatureData {
     Uri(Url),
     Tag(String),
+    Did(String),
 }
 
 #[derive(Serialize)]
@@ -132,7 +206,7 @@ fn parse_urls(s: &str) -> Vec<Facet> {
         .map(|c| {
             let url_match = c.get(1).expect("Failure at capturing URL");
             let url = Url::parse(url_match.as_str())
-                .expect(&format!("Failed to parse `{}`", url_match.as_str()));
+                .unwrap_or_else(|_| panic!("Failed to parse `{}`", url_match.as_str()));
             Facet {
                 index: Index {
                     byte_start: url_match.start(),
@@ -173,11 +247,83 @@ fn parse_facets(s: &str) -> Vec<Facet> {
     facets
 }
 
-async fn login(instance: &SocialInstance) -> Result<Session> {
+// The API calls go to the PDS hosting the account, which may not be `server`
+fn pds_url(instance: &SocialInstance) -> String {
+    server_url(instance.pds_host.as_deref().unwrap_or(&instance.server))
+}
+
+// Link to the post on the Bluesky app, or on `app_url`
+fn post_url(instance: &SocialInstance, handle: &str, record_id: &str) -> Result<Url> {
+    let app_url = instance
+        .app_url
+        .as_deref()
+        .map_or_else(|| "https://bsky.app".to_owned(), server_url);
+    Ok(Url::parse(&format!(
+        "{app_url}/profile/{handle}/post/{record_id}"
+    ))?)
+}
+
+async fn resolve_handle(
+    client: &Client,
+    instance: &SocialInstance,
+    handle: &str,
+) -> Result<String> {
+    let response = send(
+        client
+            .get(format!(
+                "{}/xrpc/com.atproto.identity.resolveHandle",
+                pds_url(instance)
+            ))
+            .query(&[("handle", handle)]),
+        instance,
+    )
+    .await?;
+
+    if response.status() != StatusCode::OK {
+        let status = response.status();
+        let text = response.text().await?;
+        bail!("{status}, {text}");
+    }
+
+    Ok(response.json::<ResolvedHandle>().await?.did)
+}
+
+// `@handle.domain` mentions, a handle that can't be resolved to a DID is left as plain text, like
+// the ones that are part of a link (`https://host/@handle.domain`)
+async fn parse_mentions(client: &Client, instance: &SocialInstance, s: &str) -> Vec<Facet> {
+    // regex from https://docs.bsky.app/docs/advanced-guides/post-richtext
+    let reg = Regex::new(r"(?:^|\W)(@(([a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?\.)+[a-zA-Z]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?))").unwrap();
+    let links = parse_urls(s);
+    let mut facets = Vec::new();
+    for c in reg.captures_iter(s) {
+        let mention_match = c.get(1).expect("Failure at capturing mention");
+        let in_link = links.iter().any(|link| {
+            (link.index.byte_start..link.index.byte_end).contains(&mention_match.start())
+        });
+        if in_link {
+            continue;
+        }
+        let handle = &mention_match.as_str()[1..];
+        match resolve_handle(client, instance, handle).await {
+            Result::Ok(did) => facets.push(Facet {
+                index: Index {
+                    byte_start: mention_match.start(),
+                    byte_end: mention_match.end(),
+                },
+                features: vec![Feature {
+                    r#type: FeatureType::mention(),
+                    data: FeatureData::Did(did),
+                }],
+            }),
+            Err(err) => warn!("Failed to resolve `@{handle}`, mention skipped: {err:#}"),
+        }
+    }
+    facets
+}
+
+async fn login(client: &Client, instance: &SocialInstance) -> Result<Session> {
     debug!("Login in {}", instance.server);
-    let Some(password) = std::env::var(&instance.token_var).ok() else {
-        bail!("`{}` env var is not defined", instance.token_var);
-    };
+    let password = instance.token()?;
 
     let identifier = match &instance.handle_var {
         Some(var) => {
@@ -189,17 +335,19 @@ async fn login(instance: &SocialInstance) -> Result<Session> {
         None => bail!("Missing `handle_var` in Bluesky definition"),
     };
 
-    let response = reqwest::Client::new()
-        .post(&format!(
-            "https://{}/xrpc/com.atproto.server.createSession",
-            instance.server
-        ))
-        .json(&Credentials {
-            identifier,
-            password,
-        })
-        .send()
-        .await?;
+    let response = send(
+        client
+            .post(format!(
+                "{}/xrpc/com.atproto.server.createSession",
+                pds_url(instance)
+            ))
+            .json(&Credentials {
+                identifier,
+                password,
+            }),
+        instance,
+    )
+    .await?;
 
     if response.status() != StatusCode::OK {
         let status = response.status();
@@ -211,25 +359,77 @@ async fn login(instance: &SocialInstance) -> Result<Session> {
     Ok(session)
 }
 
+async fn upload_blob(
+    client: &Client,
+    instance: &SocialInstance,
+    session: &Session,
+    media: &Media,
+) -> Result<serde_json::Value> {
+    let media = &*media.fit(BLOB_SIZE_LIMIT)?;
+    let response = send_media(
+        client
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.uploadBlob",
+                pds_url(instance)
+            ))
+            .bearer_auth(&session.access_jwt)
+            .header(reqwest::header::CONTENT_TYPE, media.mime)
+            .body(media.bytes.clone()),
+        instance,
+        media,
+    )
+    .await?;
+
+    if response.status() != StatusCode::OK {
+        let status = response.status();
+        let text = response.text().await?;
+        bail!("{status}, {text}");
+    }
+    Ok(response.json::<UploadedBlob>().await?.blob)
+}
+
 pub async fn push_to_bsky(
+    client: &Client,
     instance: &SocialInstance,
     status: &StatusContent,
     lang: &Lang,
-) -> Result<Option<Url>> {
+    labels: &[String],
+    media: Option<&Media>,
+) -> Result<Option<SocialPost>> {
     info!("Pushing to Bluesky");
-    let session = login(instance).await?;
-
-    let record = RecordCreation::new(&session, status.0.clone(), lang);
+    let session = login(client, instance).await?;
+
+    let mut record = RecordCreation::new(&session, status.0.clone(), lang, labels);
+    record
+        .record
+        .add_facets(parse_mentions(client, instance, &status.0).await);
+    // a media failing to be uploaded doesn't prevent the post
+    if let Some(media) = media {
+        match upload_blob(client, instance, &session, media).await {
+            Result::Ok(blob) => {
+                record.record.embed = Some(ImagesEmbed {
+                    r#type: "app.bsky.embed.images",
+                    images: vec![EmbeddedImage {
+                        alt: media.alt.clone(),
+                        image: blob,
+                    }],
+                })
+            }
+            Err(err) => warn!("Image not attached to the post: {err:#}"),
+        }
+    }
 
-    let response = reqwest::Client::new()
-        .post(&format!(
-            "https://{}/xrpc/com.atproto.repo.createRecord",
-            instance.server
-        ))
-        .bearer_auth(&session.access_jwt)
-        .json(&record)
-        .send()
-        .await?;
+    let response = send(
+        client
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.createRecord",
+                pds_url(instance)
+            ))
+            .bearer_auth(&session.access_jwt)
+            .json(&record),
+        instance,
+    )
+    .await?;
 
     if response.status() != StatusCode::OK {
         let status = response.status();
@@ -238,34 +438,324 @@ pub async fn push_to_bsky(
     }
 
     let status = response.json::<Status>().await?;
+    let (did, record_id) = parse_record_uri(&status.uri)?;
+
+    let response = send(
+        client
+            .get(format!(
+                "{}/xrpc/app.bsky.actor.getProfile",
+                pds_url(instance)
+            ))
+            .bearer_auth(&session.access_jwt)
+            .query(&[("actor", did)]),
+        instance,
+    )
+    .await?;
+
+    if response.status() != StatusCode::OK {
+        let status = response.status();
+        let text = response.text().await?;
+        bail!("Failed to get profile: {status}, {text}");
+    }
+
+    let profile = response.json::<Profile>().await?;
+
+    Ok(Some(SocialPost {
+        url: post_url(instance, &profile.handle, record_id)?,
+        id: status.uri,
+    }))
+}
+
+// Extract `did` and `record_key` from a post's `at://` URI
+fn parse_record_uri(uri: &str) -> Result<(&str, &str)> {
     let reg = Regex::new(r"at://(did:plc:.+)/app\.bsky\.feed\.post/([[:alnum:]]+)").unwrap();
-    let Some(captures) = reg.captures(&status.uri) else {
+    let Some(captures) = reg.captures(uri) else {
         bail!("Failure on retrieving `did` and `record_key`");
     };
     let did = captures.get(1).expect("No `did` in record").as_str();
     let record_id = captures.get(2).expect("No `record_key` in record").as_str();
+    Ok((did, record_id))
+}
 
-    let response = reqwest::Client::new()
-        .get(format!(
-            "https://{}/xrpc/app.bsky.actor.getProfile",
-            instance.server
-        ))
-        .bearer_auth(&session.access_jwt)
-        .query(&[("actor", did)])
-        .send()
-        .await?;
+#[derive(Serialize)]
+struct RecordDeletion<'a> {
+    repo: &'a str,
+    collection: &'static str,
+    rkey: &'a str,
+}
+
+// Log in without posting, to know the app password still works. Gives the handle logged in as
+pub async fn verify_bsky(client: &Client, instance: &SocialInstance) -> Result<String> {
+    let session = login(client, instance).await?;
+    Ok(session.handle.unwrap_or(session.did))
+}
+
+pub async fn delete_from_bsky(client: &Client, instance: &SocialInstance, uri: &str) -> Result<()> {
+    let session = login(client, instance).await?;
+    let (did, record_id) = parse_record_uri(uri)?;
+
+    let response = send(
+        client
+            .post(format!(
+                "{}/xrpc/com.atproto.repo.deleteRecord",
+                pds_url(instance)
+            ))
+            .bearer_auth(&session.access_jwt)
+            .json(&RecordDeletion {
+                repo: did,
+                collection: "app.bsky.feed.post",
+                rkey: record_id,
+            }),
+        instance,
+    )
+    .await?;
 
     if response.status() != StatusCode::OK {
         let status = response.status();
         let text = response.text().await?;
-        bail!("Failed to get profile: {status}, {text}");
+        bail!("Failed to delete post: {status}, {text}");
     }
 
-    let profile = response.json::<Profile>().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        config::{SocialApi, SocialInstance},
+        test_dir,
+    };
+
+    use super::{parse_mentions, push_to_bsky, Client, Lang, Record, StatusContent};
+
+    fn instance(server: &MockServer, prefix: &str) -> SocialInstance {
+        let handle_var = format!("{prefix}_HANDLE");
+        let token_var = format!("{prefix}_PWD");
+        std::env::set_var(&handle_var, "me.bsky.social");
+        std::env::set_var(&token_var, "app-password");
+        SocialInstance {
+            server: server.uri(),
+            api: SocialApi::Bluesky,
+            token_var,
+            token_file: None,
+            handle_var: Some(handle_var),
+            langs: Vec::new(),
+            max_retries: 0,
+            pds_host: None,
+            app_url: None,
+        }
+    }
+
+    async fn mount_login(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "accessJwt": "jwt",
+                "did": "did:plc:abc",
+            })))
+            .mount(server)
+            .await;
+    }
 
-    let url = format!(
-        "https://bsky.app/profile/{}/post/{record_id}",
-        profile.handle
-    );
-    Ok(Some(Url::parse(&url)?))
+    async fn push(instance: &SocialInstance) -> anyhow::Result<Option<super::SocialPost>> {
+        let status = StatusContent("New post! https://example.com/posts/new/ #rust".to_string());
+        push_to_bsky(
+            &Client::new(),
+            instance,
+            &status,
+            &Lang("en".to_string()),
+            &[],
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_push_success() {
+        let server = MockServer::start().await;
+        mount_login(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.createRecord"))
+            .and(header("Authorization", "Bearer jwt"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uri": "at://did:plc:abc/app.bsky.feed.post/rkey1",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "handle": "me.bsky.social",
+            })))
+            .mount(&server)
+            .await;
+
+        let post = push(&instance(&server, "EMILE_TEST_BSKY_SUCCESS"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            post.url.as_str(),
+            "https://bsky.app/profile/me.bsky.social/post/rkey1"
+        );
+        assert_eq!(post.id, "at://did:plc:abc/app.bsky.feed.post/rkey1");
+    }
+
+    #[tokio::test]
+    async fn test_push_custom_pds() {
+        let pds = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .and(body_partial_json(serde_json::json!({
+                "password": "app-password",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "accessJwt": "jwt",
+                "did": "did:plc:abc",
+            })))
+            .expect(1)
+            .mount(&pds)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.createRecord"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uri": "at://did:plc:abc/app.bsky.feed.post/rkey1",
+            })))
+            .expect(1)
+            .mount(&pds)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/app.bsky.actor.getProfile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "handle": "me.example.com",
+            })))
+            .mount(&pds)
+            .await;
+
+        let dir = test_dir();
+        let token_file = dir.path().join("pds_pwd");
+        std::fs::write(&token_file, "app-password\n").unwrap();
+        let mut instance = instance(&pds, "EMILE_TEST_BSKY_PDS");
+        // only the PDS is contacted
+        instance.server = "http://127.0.0.1:1".to_string();
+        instance.pds_host = Some(pds.uri());
+        instance.app_url = Some("https://app.example.com/".to_string());
+        instance.token_var = String::new();
+        instance.token_file = Some(token_file.clone());
+
+        let post = push(&instance).await.unwrap().unwrap();
+        assert_eq!(
+            post.url.as_str(),
+            "https://app.example.com/profile/me.example.com/post/rkey1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_auth_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.server.createSession"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("Invalid identifier"))
+            .mount(&server)
+            .await;
+
+        let err = push(&instance(&server, "EMILE_TEST_BSKY_AUTH"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to login"));
+        assert!(err.contains("Invalid identifier"));
+    }
+
+    #[tokio::test]
+    async fn test_push_server_error() {
+        let server = MockServer::start().await;
+        mount_login(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.createRecord"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Record is invalid"))
+            .mount(&server)
+            .await;
+
+        let err = push(&instance(&server, "EMILE_TEST_BSKY_ERROR"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Record is invalid"));
+    }
+
+    #[tokio::test]
+    async fn test_push_invalid_record_uri() {
+        let server = MockServer::start().await;
+        mount_login(&server).await;
+        Mock::given(method("POST"))
+            .and(path("/xrpc/com.atproto.repo.createRecord"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uri": "https://not-an-at-uri",
+            })))
+            .mount(&server)
+            .await;
+
+        assert!(push(&instance(&server, "EMILE_TEST_BSKY_URI"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parse_mentions() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.identity.resolveHandle"))
+            .and(query_param("handle", "friend.bsky.social"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "did": "did:plc:friend",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/xrpc/com.atproto.identity.resolveHandle"))
+            .and(query_param("handle", "unknown.example.com"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Unable to resolve handle"))
+            .mount(&server)
+            .await;
+
+        let text =
+            "Written with @friend.bsky.social and @unknown.example.com, mail me@example.com, \
+             see https://example.com/@friend.bsky.social";
+        let instance = instance(&server, "EMILE_TEST_BSKY_MENTIONS");
+        let facets = parse_mentions(&Client::new(), &instance, text).await;
+        assert_eq!(
+            serde_json::to_value(&facets).unwrap(),
+            serde_json::json!([{
+                "index": { "byteStart": 13, "byteEnd": 32 },
+                "features": [{ "$type": "app.bsky.richtext.facet#mention", "did": "did:plc:friend" }],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_record_labels() {
+        let lang = Lang("en".to_string());
+        let record = serde_json::to_value(Record::new("text".to_string(), &lang, &[])).unwrap();
+        assert!(record.get("labels").is_none());
+
+        let labels = vec![
+            "!no-unauthenticated".to_string(),
+            "graphic-media".to_string(),
+        ];
+        let record = serde_json::to_value(Record::new("text".to_string(), &lang, &labels)).unwrap();
+        assert_eq!(
+            record["labels"],
+            serde_json::json!({
+                "$type": "com.atproto.label.defs#selfLabels",
+                "values": [{ "val": "!no-unauthenticated" }, { "val": "graphic-media" }],
+            })
+        );
+    }
 }
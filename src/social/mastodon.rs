@@ -1,16 +1,23 @@
+use std::borrow::Cow;
+
 use anyhow::{bail, Result};
-use reqwest::{StatusCode, Url};
+use reqwest::{Client, StatusCode, Url};
 use serde_derive::{Deserialize, Serialize};
 use tracing::{error, info, warn};
 
 use crate::config::SocialInstance;
 
-use super::{Lang, StatusContent};
+use super::{
+    media_form, send, send_media, server_url, Lang, Media, Poll, SocialPost, StatusContent,
+};
 
 #[derive(Deserialize, Debug)]
 struct Status {
     id: String,
+    // ActivityPub id of the toot
     uri: String,
+    // web page of the toot, `null` for some statuses
+    url: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -18,37 +25,169 @@ struct Toot<'a> {
     status: &'a str,
     visibility: &'static str,
     language: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    poll: Option<&'a Poll>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    media_ids: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Instance {
+    configuration: InstanceConfiguration,
+}
+
+#[derive(Deserialize, Debug)]
+struct InstanceConfiguration {
+    media_attachments: MediaLimits,
+}
+
+#[derive(Deserialize, Debug)]
+struct MediaLimits {
+    supported_mime_types: Vec<String>,
+    image_size_limit: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct Account {
+    acct: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Attachment {
+    id: String,
+}
+
+// `media` within the limits of the instance, if it tells them: downscaled if too big, and as a JPEG
+// if its type is not supported
+async fn fit_media_limits<'a>(
+    client: &Client,
+    instance: &SocialInstance,
+    media: &'a Media,
+) -> Result<Cow<'a, Media>> {
+    let res = send(
+        client.get(format!("{}/api/v2/instance", server_url(&instance.server))),
+        instance,
+    )
+    .await?;
+    let Some(limits) = res
+        .json::<Instance>()
+        .await
+        .ok()
+        .map(|instance| instance.configuration.media_attachments)
+    else {
+        return Ok(Cow::Borrowed(media));
+    };
+    let is_supported = |mime: &str| limits.supported_mime_types.iter().any(|m| m == mime);
+    if is_supported(media.mime) {
+        media.fit(limits.image_size_limit)
+    } else if is_supported("image/jpeg") {
+        info!("`{}` images are not supported, sent as JPEG", media.mime);
+        Ok(Cow::Owned(media.to_jpeg(limits.image_size_limit)?))
+    } else {
+        bail!("`{}` images are not supported", media.mime);
+    }
+}
+
+async fn upload_media(
+    client: &Client,
+    instance: &SocialInstance,
+    token: &str,
+    media: &Media,
+) -> Result<String> {
+    let media = &*fit_media_limits(client, instance, media).await?;
+    let res = send_media(
+        client
+            .post(format!("{}/api/v2/media", server_url(&instance.server)))
+            .bearer_auth(token)
+            .multipart(media_form(media)?),
+        instance,
+        media,
+    )
+    .await?;
+
+    // 202 when the media is still being processed, it can be attached anyway
+    if !matches!(res.status(), StatusCode::OK | StatusCode::ACCEPTED) {
+        let status = res.status();
+        let text = res.text().await?;
+        bail!("{status}, {text}");
+    }
+    Ok(res.json::<Attachment>().await?.id)
+}
+
+// Bookmark the toot `id` to avoid deletion and for easy retrieval
+async fn bookmark(client: &Client, instance: &SocialInstance, token: &str, id: &str) -> Result<()> {
+    let res = send(
+        client
+            .post(format!(
+                "{}/api/v1/statuses/{id}/bookmark",
+                server_url(&instance.server)
+            ))
+            .bearer_auth(token),
+        instance,
+    )
+    .await?;
+
+    if res.status() != StatusCode::OK {
+        let status = res.status();
+        let text = res.text().await?;
+        bail!("{status}, {text}");
+    }
+    Ok(())
 }
 
 pub async fn push_to_mastodon(
+    client: &Client,
     instance: &SocialInstance,
     status: &StatusContent,
     language: &Lang,
-) -> Result<Option<Url>> {
+    poll: Option<&Poll>,
+    media: Option<&Media>,
+    // what the Idempotency-Key is derived from, the text of the toot if not set
+    idempotency_src: Option<&str>,
+) -> Result<Option<SocialPost>> {
     info!("Push to social Mastodon");
 
-    let Some(token) = std::env::var(&instance.token_var).ok() else {
-        error!("`{}` env var is not defined", instance.token_var);
-        return Ok(None);
+    let token = match instance.token() {
+        Ok(token) => token,
+        Err(err) => {
+            error!("{err:#}");
+            return Ok(None);
+        }
     };
 
+    // a media failing to be uploaded doesn't prevent the toot
+    let mut media_ids = Vec::new();
+    if media.is_some() && poll.is_some() {
+        warn!("Image not attached to the toot: Mastodon doesn't accept it along a poll");
+    } else if let Some(media) = media {
+        match upload_media(client, instance, &token, media).await {
+            Ok(id) => media_ids.push(id),
+            Err(err) => warn!("Image not attached to the toot: {err:#}"),
+        }
+    }
+
     // publish toot
     let toot = Toot {
         status,
         visibility: "public",
         language,
+        poll,
+        media_ids,
     };
 
     use sha2::{Digest, Sha256};
-    let hash = format!("{:x}", Sha256::digest(toot.status.as_bytes()));
+    let idempotency_src = idempotency_src.unwrap_or(toot.status);
+    let hash = format!("{:x}", Sha256::digest(idempotency_src.as_bytes()));
 
-    let res = reqwest::Client::new()
-        .post(&format!("https://{}/api/v1/statuses", instance.server))
-        .bearer_auth(&token)
-        .header("Idempotency-Key", hash)
-        .json(&toot)
-        .send()
-        .await?;
+    let res = send(
+        client
+            .post(format!("{}/api/v1/statuses", server_url(&instance.server)))
+            .bearer_auth(&token)
+            .header("Idempotency-Key", hash)
+            .json(&toot),
+        instance,
+    )
+    .await?;
 
     if res.status() != StatusCode::OK {
         let status = res.status();
@@ -58,21 +197,567 @@ pub async fn push_to_mastodon(
 
     let status = res.json::<Status>().await?;
 
-    // bookmark it to avoid deletion and for easy retrieval
-    let res = reqwest::Client::new()
-        .post(&format!(
-            "https://{}/api/v1/statuses/{}/bookmark",
-            instance.server, status.id
-        ))
-        .bearer_auth(token)
-        .send()
-        .await?;
+    // the toot is posted, failing to bookmark it must not lose its link
+    if let Err(err) = bookmark(client, instance, &token, &status.id).await {
+        warn!("Failed to bookmark toot: {err:#}");
+    }
+
+    // the link put in the blog post is the one readers can open, the ActivityPub id if none
+    let url = status.url.as_deref().unwrap_or(&status.uri);
+    Ok(Some(SocialPost {
+        url: Url::parse(url)?,
+        id: status.id,
+    }))
+}
+
+// Check the token without posting. Gives the account it belongs to
+pub async fn verify_mastodon(client: &Client, instance: &SocialInstance) -> Result<String> {
+    let token = instance.token()?;
+
+    let res = send(
+        client
+            .get(format!(
+                "{}/api/v1/accounts/verify_credentials",
+                server_url(&instance.server)
+            ))
+            .bearer_auth(token),
+        instance,
+    )
+    .await?;
+
+    if res.status() != StatusCode::OK {
+        let status = res.status();
+        let text = res.text().await?;
+        bail!("Invalid token: {status}, {text}");
+    }
+
+    Ok(res.json::<Account>().await?.acct)
+}
+
+pub async fn delete_from_mastodon(
+    client: &Client,
+    instance: &SocialInstance,
+    id: &str,
+) -> Result<()> {
+    let token = instance.token()?;
+
+    let res = send(
+        client
+            .delete(format!(
+                "{}/api/v1/statuses/{id}",
+                server_url(&instance.server)
+            ))
+            .bearer_auth(token),
+        instance,
+    )
+    .await?;
 
     if res.status() != StatusCode::OK {
         let status = res.status();
         let text = res.text().await?;
-        warn!("Failed to bookmark toot: {status}, {text}");
+        bail!("Failed to delete toot: {status}, {text}");
     }
 
-    Ok(Some(Url::parse(&status.uri)?))
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path},
+        Mock, MockServer, Request, ResponseTemplate,
+    };
+
+    use crate::{
+        config::{SocialApi, SocialInstance},
+        social::tests::png_media,
+    };
+
+    use super::{push_to_mastodon, verify_mastodon, Client, Lang, Poll, StatusContent};
+
+    fn instance(server: &MockServer, token_var: &str) -> SocialInstance {
+        std::env::set_var(token_var, "token");
+        SocialInstance {
+            server: server.uri(),
+            api: SocialApi::Mastodon,
+            token_var: token_var.to_string(),
+            token_file: None,
+            handle_var: None,
+            langs: Vec::new(),
+            max_retries: 0,
+            pds_host: None,
+            app_url: None,
+        }
+    }
+
+    async fn push(instance: &SocialInstance) -> anyhow::Result<Option<super::SocialPost>> {
+        let status = StatusContent("New post!".to_string());
+        push_to_mastodon(
+            &Client::new(),
+            instance,
+            &status,
+            &Lang("en".to_string()),
+            None,
+            None,
+            None,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_push_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(header("Authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "42",
+                "uri": "https://mastodon.example/users/me/statuses/42",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/42/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let post = push(&instance(&server, "EMILE_TEST_MASTODON_SUCCESS"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            post.url.as_str(),
+            "https://mastodon.example/users/me/statuses/42"
+        );
+        assert_eq!(post.id, "42");
+    }
+
+    #[tokio::test]
+    async fn test_push_bookmark_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "42",
+                "uri": "https://mastodon.example/users/me/statuses/42",
+            })))
+            .mount(&server)
+            .await;
+        // redirected to a closed port: the request itself fails
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/42/bookmark"))
+            .respond_with(
+                ResponseTemplate::new(307).insert_header("Location", "http://127.0.0.1:9/"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let post = push(&instance(&server, "EMILE_TEST_MASTODON_BOOKMARK"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.id, "42");
+    }
+
+    #[tokio::test]
+    async fn test_push_web_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "42",
+                "uri": "https://mastodon.example/users/me/statuses/42",
+                "url": "https://mastodon.example/@me/42",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/42/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let post = push(&instance(&server, "EMILE_TEST_MASTODON_WEB_URL"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(post.url.as_str(), "https://mastodon.example/@me/42");
+        assert_eq!(post.id, "42");
+    }
+
+    #[tokio::test]
+    async fn test_push_idempotency_src() {
+        use sha2::{Digest, Sha256};
+
+        let server = MockServer::start().await;
+        let key = format!("{:x}", Sha256::digest(b"my-post/en"));
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(header("Idempotency-Key", key.as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "44",
+                "uri": "https://mastodon.example/users/me/statuses/44",
+            })))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/44/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        // the key doesn't change when the text does
+        let instance = instance(&server, "EMILE_TEST_MASTODON_IDEMPOTENCY");
+        for text in ["New post!", "New post, fixed!"] {
+            push_to_mastodon(
+                &Client::new(),
+                &instance,
+                &StatusContent(text.to_string()),
+                &Lang("en".to_string()),
+                None,
+                None,
+                Some("my-post/en"),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "45",
+                "uri": "https://mastodon.example/users/me/statuses/45",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/45/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let mut instance = instance(&server, "EMILE_TEST_MASTODON_RATE_LIMIT");
+        instance.max_retries = 1;
+        let post = push(&instance).await.unwrap().unwrap();
+        assert_eq!(post.id, "45");
+    }
+
+    #[tokio::test]
+    async fn test_push_with_poll() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(body_partial_json(serde_json::json!({
+                "poll": { "options": ["Yes", "No"], "expires_in": 3600 },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "43",
+                "uri": "https://mastodon.example/users/me/statuses/43",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poll = Poll {
+            options: vec!["Yes".to_string(), "No".to_string()],
+            expires_in: 3600,
+        };
+        let post = push_to_mastodon(
+            &Client::new(),
+            &instance(&server, "EMILE_TEST_MASTODON_POLL"),
+            &StatusContent("Vote!".to_string()),
+            &Lang("en".to_string()),
+            Some(&poll),
+            None,
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(post.id, "43");
+    }
+
+    #[tokio::test]
+    async fn test_push_poll_drops_media() {
+        let server = MockServer::start().await;
+        // Mastodon answers 422 to a toot with both a poll and a media
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(body_partial_json(serde_json::json!({
+                "poll": { "options": ["Yes", "No"], "expires_in": 3600 },
+            })))
+            .and(|req: &Request| {
+                serde_json::from_slice::<serde_json::Value>(&req.body)
+                    .is_ok_and(|toot| toot.get("media_ids").is_none())
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "46",
+                "uri": "https://mastodon.example/users/me/statuses/46",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poll = Poll {
+            options: vec!["Yes".to_string(), "No".to_string()],
+            expires_in: 3600,
+        };
+        let post = push_to_mastodon(
+            &Client::new(),
+            &instance(&server, "EMILE_TEST_MASTODON_POLL_MEDIA"),
+            &StatusContent("Vote!".to_string()),
+            &Lang("en".to_string()),
+            Some(&poll),
+            Some(&png_media(16)),
+            None,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(post.id, "46");
+    }
+
+    #[tokio::test]
+    async fn test_push_with_media() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "configuration": { "media_attachments": {
+                    "supported_mime_types": ["image/png"],
+                    "image_size_limit": 1_000_000,
+                }},
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(202).set_body_json(serde_json::json!({
+                "id": "7",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .and(body_partial_json(serde_json::json!({ "media_ids": ["7"] })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "44",
+                "uri": "https://mastodon.example/users/me/statuses/44",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let instance = instance(&server, "EMILE_TEST_MASTODON_MEDIA");
+        let push_with = |media| {
+            let instance = &instance;
+            async move {
+                push_to_mastodon(
+                    &Client::new(),
+                    instance,
+                    &StatusContent("Look!".to_string()),
+                    &Lang("en".to_string()),
+                    None,
+                    Some(&media),
+                    None,
+                )
+                .await
+            }
+        };
+        let post = push_with(png_media(16)).await.unwrap().unwrap();
+        assert_eq!(post.id, "44");
+
+        // over the instance's limit even downscaled, the toot is posted without the image
+        server.reset().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "45",
+                "uri": "https://mastodon.example/users/me/statuses/45",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v2/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "configuration": { "media_attachments": {
+                    "supported_mime_types": ["image/png"],
+                    "image_size_limit": 2,
+                }},
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/media"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let post = push_with(png_media(16)).await.unwrap().unwrap();
+        assert_eq!(post.id, "45");
+
+        // a PNG over the limit or not supported is sent as a downscaled JPEG
+        for (mime, limit) in [("image/png", 40_000), ("image/jpeg", 1_000_000)] {
+            server.reset().await;
+            Mock::given(method("GET"))
+                .and(path("/api/v2/instance"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "configuration": { "media_attachments": {
+                        "supported_mime_types": [mime],
+                        "image_size_limit": limit,
+                    }},
+                })))
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/api/v2/media"))
+                // the body is not UTF-8 with the image in it
+                .and(|req: &Request| req.body.windows(10).any(|part| part == b"image/jpeg"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": "8",
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("POST"))
+                .and(path("/api/v1/statuses"))
+                .and(body_partial_json(serde_json::json!({ "media_ids": ["8"] })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "id": "46",
+                    "uri": "https://mastodon.example/users/me/statuses/46",
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+            let post = push_with(png_media(256)).await.unwrap().unwrap();
+            assert_eq!(post.id, "46");
+            server.verify().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_auth_failure() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("The access token is invalid"))
+            .mount(&server)
+            .await;
+
+        let err = push(&instance(&server, "EMILE_TEST_MASTODON_AUTH"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("401"));
+        assert!(err.contains("The access token is invalid"));
+    }
+
+    #[tokio::test]
+    async fn test_push_server_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("Something went wrong"))
+            .mount(&server)
+            .await;
+
+        let err = push(&instance(&server, "EMILE_TEST_MASTODON_ERROR"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Something went wrong"));
+    }
+
+    #[tokio::test]
+    async fn test_push_invalid_uri() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "42",
+                "uri": "not a url",
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/42/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        assert!(push(&instance(&server, "EMILE_TEST_MASTODON_URI"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/accounts/verify_credentials"))
+            .and(header("Authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "acct": "me",
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/accounts/verify_credentials"))
+            .respond_with(ResponseTemplate::new(401).set_body_string("The access token is invalid"))
+            .mount(&server)
+            .await;
+
+        let instance = instance(&server, "EMILE_TEST_MASTODON_VERIFY");
+        let client = Client::new();
+        assert_eq!(verify_mastodon(&client, &instance).await.unwrap(), "me");
+        let err = verify_mastodon(&client, &instance).await.unwrap_err();
+        assert!(
+            err.to_string().contains("The access token is invalid"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_without_token() {
+        let server = MockServer::start().await;
+        let instance = SocialInstance {
+            server: server.uri(),
+            api: SocialApi::Mastodon,
+            token_var: "EMILE_TEST_MASTODON_UNDEFINED".to_string(),
+            token_file: None,
+            handle_var: None,
+            langs: Vec::new(),
+            max_retries: 0,
+            pds_host: None,
+            app_url: None,
+        };
+
+        assert!(push(&instance).await.unwrap().is_none());
+    }
 }
@@ -1,38 +1,86 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt::Display,
     fs::File,
     io::Read,
     ops::Deref,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, bail, Result};
-use reqwest::Url;
-use serde_derive::Deserialize;
-use tracing::{error, info};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future::join_all;
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage};
+use reqwest::{
+    multipart::{Form, Part},
+    Client, RequestBuilder, Response, StatusCode, Url,
+};
+use serde_derive::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    config::{SocialApi, SocialCfg},
-    social::mastodon::push_to_mastodon,
+    config::{
+        IdempotencyKey, LinkCheck, LinkInjection, LinkPlacement, SocialApi, SocialCfg,
+        SocialInstance, TagCase,
+    },
+    post::{frontmatter, is_delimiter, set_extra_field},
+    social::mastodon::{delete_from_mastodon, push_to_mastodon, verify_mastodon},
 };
 
-use self::bluesky::push_to_bsky;
+use self::bluesky::{delete_from_bsky, push_to_bsky, verify_bsky};
 
 mod bluesky;
 mod mastodon;
 
-#[derive(Debug, Deserialize)]
-struct Tags {
-    tags: Vec<String>,
-}
+// placeholders filled in the social post templates, and in the link template
+const STATUS_PLACEHOLDERS: [&str; 4] = ["title", "description", "link", "tags"];
+const LINK_PLACEHOLDERS: [&str; 1] = ["links"];
 
-impl std::ops::Deref for Tags {
-    type Target = Vec<String>;
+// key in the frontmatter's `[extra]` table where the published social posts are recorded
+const SOCIAL_RECORDS_KEY: &str = "emile_social";
+// key in the frontmatter's `[extra]` table where the links are put with `link_placement = "frontmatter"`
+const SOCIAL_LINKS_KEY: &str = "social_links";
+// around the links snippet added with `link_injection = "append"` or "prepend", so publishing again
+// replaces it instead of adding another one
+const LINKS_START_MARKER: &str = "<!-- emile_social -->";
+const LINKS_END_MARKER: &str = "<!-- /emile_social -->";
 
-    fn deref(&self) -> &Self::Target {
-        &self.tags
+// smallest width or height an image is downscaled to, to fit in the size limit of an instance
+const MIN_IMAGE_SIDE: u32 = 64;
+// quality of the JPEG of a downscaled image
+const JPEG_QUALITY: u8 = 85;
+
+// maximum duration of a request to a social server
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+// longest `Retry-After` of a rate limited request waited for, the request fails beyond it
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+// wait before sending again a rate limited request without `Retry-After`
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+// Client for the social requests, built once by the caller from `cfg` and passed down to reuse its
+// connections
+pub fn http_client(cfg: &SocialCfg) -> Result<Client> {
+    // a redirected POST could be turned into a GET or sent to an unexpected server
+    let mut builder = Client::builder()
+        .timeout(HTTP_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(proxy) = &cfg.http_proxy {
+        builder = builder.proxy(
+            reqwest::Proxy::all(proxy)
+                .with_context(|| format!("Invalid `http_proxy` `{proxy}`"))?,
+        );
+    }
+    if let Some(cert) = &cfg.extra_ca_cert {
+        let pem = std::fs::read(cert)
+            .with_context(|| format!("Failed to read `{}`", cert.to_string_lossy()))?;
+        builder =
+            builder
+                .add_root_certificate(reqwest::Certificate::from_pem(&pem).with_context(|| {
+                    format!("Invalid certificate `{}`", cert.to_string_lossy())
+                })?);
     }
+    Ok(builder.build()?)
 }
 
 struct Title(String);
@@ -45,6 +93,7 @@ impl Deref for Title {
     }
 }
 
+#[derive(Clone)]
 pub struct Lang(String);
 
 impl Deref for Lang {
@@ -73,6 +122,39 @@ impl Deref for TagsList {
 
 pub struct StatusContent(String);
 
+// link to a social post in the frontmatter, for the theme to display it
+#[derive(Debug, Serialize, Deserialize)]
+struct SocialLink {
+    name: String,
+    url: String,
+}
+
+// A post published on a social instance
+#[derive(Debug)]
+pub struct SocialPost {
+    url: Url,
+    // what is needed to delete the post: status id on Mastodon, record URI on Bluesky
+    id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SocialRecord {
+    api: SocialApi,
+    server: String,
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordsFront {
+    extra: Option<RecordsExtra>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RecordsExtra {
+    emile_social: Option<Vec<SocialRecord>>,
+    social_links: Option<Vec<SocialLink>>,
+}
+
 impl Deref for StatusContent {
     type Target = String;
 
@@ -81,80 +163,206 @@ impl Deref for StatusContent {
     }
 }
 
-fn extract_title_lang_tags(content: &str, config: &SocialCfg) -> Result<(Title, Lang, TagsList)> {
-    let mut title = String::new();
-    let mut lang = String::new();
-    let mut returned_tags = Vec::new();
+// Delay asked by a rate limited response, in seconds or as an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+            Some(
+                (date.to_utc() - chrono::Utc::now())
+                    .to_std()
+                    .unwrap_or_default(),
+            )
+        }
+    }
+}
+
+// Send `request` to `instance`, sending it again up to `max_retries` times when the server answers
+// with `429 Too Many Requests`, after the delay it asks for. Redirections are errors, the `server`
+// of the instance being outdated.
+async fn send(request: RequestBuilder, instance: &SocialInstance) -> Result<Response> {
+    let mut request = request;
+    let mut attempt = 0;
+    loop {
+        let next = request.try_clone();
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .unwrap_or_default();
+            bail!(
+                "`{}` redirects to `{location}` ({status}), check the `server` of the instance",
+                response.url()
+            );
+        }
+        if status != StatusCode::TOO_MANY_REQUESTS || attempt >= instance.max_retries {
+            return Ok(response);
+        }
+        let delay = retry_after(&response).unwrap_or(DEFAULT_RETRY_AFTER);
+        let Some(next) = next.filter(|_| delay <= MAX_RETRY_AFTER) else {
+            return Ok(response);
+        };
+        attempt += 1;
+        warn!(
+            "Rate limited by `{}`, retrying in {}s ({attempt}/{})",
+            instance.server,
+            delay.as_secs(),
+            instance.max_retries
+        );
+        tokio::time::sleep(delay).await;
+        request = next;
+    }
+}
+
+// Send the upload of `media` with `send`, logging it as big images can take a while to upload
+async fn send_media(
+    request: RequestBuilder,
+    instance: &SocialInstance,
+    media: &Media,
+) -> Result<Response> {
+    info!("Uploading `{}` to `{}`", media.file_name, instance.server);
+    debug!(
+        "`{}` is {} bytes of {}",
+        media.file_name,
+        media.bytes.len(),
+        media.mime
+    );
+    let start = Instant::now();
+    let response = send(request, instance).await?;
+    info!(
+        "Uploaded `{}` to `{}` in {:.1}s: {}",
+        media.file_name,
+        instance.server,
+        start.elapsed().as_secs_f32(),
+        response.status()
+    );
+    Ok(response)
+}
 
-    // extract title and lang
+// Multipart form of `media` and its alt text, as `file` and `description`
+fn media_form(media: &Media) -> Result<Form> {
+    let file = Part::bytes(media.bytes.clone())
+        .file_name(media.file_name.clone())
+        .mime_str(media.mime)?;
+    Ok(Form::new()
+        .part("file", file)
+        .text("description", media.alt.clone()))
+}
+
+// `server` is a host, but can carry its scheme (ex: `http://localhost:8080`) for non-HTTPS servers
+fn server_url(server: &str) -> String {
+    if server.starts_with("http://") || server.starts_with("https://") {
+        server.trim_end_matches('/').to_owned()
+    } else {
+        format!("https://{server}")
+    }
+}
+
+// Title of the post as put in its social post, read line by line
+pub fn post_title(content: &str) -> Result<String> {
+    let mut title = String::new();
     for line in content.lines() {
         let line = line.trim();
         if line.starts_with("title") {
-            let parts: Vec<&str> = line.split('=').collect();
+            let parts: Vec<&str> = line.splitn(2, '=').collect();
             title = parts
                 .get(1)
                 .map(|t| t.replace('"', "").trim().to_string())
                 .ok_or_else(|| anyhow!("No title after `title` line"))?;
-        } else if line.starts_with("tags") {
-            let tags = match toml::from_str::<Tags>(line) {
-                Ok(tags) => Some(tags),
-                Err(e) => {
-                    error!("Error in push_to_social: {}", e);
-                    None
-                }
-            };
-
-            // search if a lang tag is present to change the lang of the toot
-            lang = if let Some(tags) = tags.as_ref() {
-                let lang = config
-                    .tag_lang
-                    .as_ref()
-                    .map(|langs| {
-                        for tag_lang in langs.iter() {
-                            let lang = tags.iter().find_map(|tag| {
-                                if tag.as_str() == tag_lang.tag.as_str() {
-                                    Some(tag_lang.lang.to_owned())
-                                } else {
-                                    None
-                                }
-                            });
-
-                            if let Some(lang) = lang {
-                                return lang;
-                            }
-                        }
-
-                        config.default_lang.to_owned()
-                    })
-                    .unwrap_or(config.default_lang.to_owned());
-
-                // slugify tags
-                returned_tags = tags
-                    .iter()
-                    .filter_map(|tag| {
-                        if !config.filtered_tag.contains(tag) {
-                            let tag = slug::slugify(tag);
-                            let parts = tag.split('-');
-                            let tag = parts.fold(String::new(), |mut acc, part| {
-                                acc.push_str(&part[0..1].to_uppercase());
-                                acc.push_str(&part[1..]);
-                                acc
-                            });
-                            Some(tag)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                lang
-            } else {
-                config.default_lang.clone()
-            };
         }
     }
+    Ok(title)
+}
+
+fn extract_title_lang_tags(content: &str, config: &SocialCfg) -> Result<(Title, Lang, TagsList)> {
+    let title = post_title(content)?;
+
+    let tags = post_tags(content, config);
+    // search if a lang tag is present to change the lang of the toot
+    let tag_lang = config
+        .tag_lang
+        .iter()
+        .flatten()
+        .find(|tag_lang| tags.contains(&tag_lang.tag))
+        .map(|tag_lang| tag_lang.lang.clone());
+    // slugify tags
+    let returned_tags = tags
+        .iter()
+        .filter(|tag| !config.filtered_tag.contains(tag))
+        .map(|tag| format_tag(tag, config.tag_case))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    let lang = front_lang(content, config)
+        .or(tag_lang)
+        .unwrap_or_else(|| config.default_lang.clone());
     Ok((Title(title), Lang(lang), TagsList(returned_tags)))
 }
 
+// Language and hashtags of the social post of `content`
+pub fn post_lang_hashtags(content: &str, config: &SocialCfg) -> Result<(String, Vec<String>)> {
+    let (_, lang, tags) = extract_title_lang_tags(content, config)?;
+    Ok((lang.0, tags.0))
+}
+
+// Language of the post, as used for its social post
+pub fn post_lang(content: &str, config: &SocialCfg) -> Option<String> {
+    extract_title_lang_tags(content, config)
+        .ok()
+        .map(|(_, lang, _)| lang.0)
+}
+
+// Language of the post found at `lang_key` in its frontmatter
+fn front_lang(content: &str, config: &SocialCfg) -> Option<String> {
+    let key = config.lang_key.as_ref()?;
+    let front = toml::Value::Table(front_table(content)?);
+    key.split('.')
+        .try_fold(&front, |value, part| value.get(part))?
+        .as_str()
+        .map(str::to_owned)
+}
+
+// Hashtag of `tag`, empty if it has no letter or digit
+fn format_tag(tag: &str, case: TagCase) -> String {
+    if case == TagCase::Original {
+        // anything else would end the hashtag
+        return tag
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+    }
+
+    let tag = slug::slugify(tag);
+    tag.split('-')
+        .filter(|part| !part.is_empty())
+        .fold(String::new(), |mut acc, part| {
+            let capitalize = match case {
+                TagCase::Pascal => true,
+                TagCase::Camel => !acc.is_empty(),
+                _ => false,
+            };
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) if capitalize => {
+                    acc.extend(first.to_uppercase());
+                    acc.push_str(chars.as_str());
+                }
+                _ => acc.push_str(part),
+            }
+            acc
+        })
+}
+
 fn read_template(path: &Path, social: &SocialCfg, cur_lang: &Lang) -> Result<String> {
     fn read_file(path: &Path) -> Result<String> {
         let mut file = File::open(path)?;
@@ -186,46 +394,365 @@ fn path_with_lang(path: &Path, lang: &Lang) -> Result<PathBuf> {
     )))
 }
 
-fn create_toot_content(
+// URL of the post published at `dest` on the website
+pub fn post_link(cfg: &SocialCfg, dest: &Path) -> String {
+    let post_path = cfg
+        .section_path
+        .as_ref()
+        .and_then(|sections| {
+            let dir = dest.parent()?;
+            sections
+                .iter()
+                .find(|section| section.dir == dir)
+                .map(|section| section.path.as_str())
+        })
+        .unwrap_or(&cfg.post_path)
+        .trim_matches('/');
+
+    let slug = dest
+        .file_stem()
+        .expect("Should have file_name by now")
+        .to_string_lossy();
+
+    if post_path.is_empty() {
+        format!("{}/{slug}/", cfg.base_url)
+    } else {
+        format!("{}/{post_path}/{slug}/", cfg.base_url)
+    }
+}
+
+fn front_table(content: &str) -> Option<toml::Table> {
+    toml::from_str::<toml::Table>(&frontmatter(content).ok()?).ok()
+}
+
+// `description` of the post, or its `summary`, searched at top level then in `[extra]`
+fn extract_description(content: &str) -> String {
+    let Some(front) = front_table(content) else {
+        return String::new();
+    };
+    ["description", "summary"]
+        .iter()
+        .find_map(|key| {
+            front
+                .get(*key)
+                .or_else(|| front.get("extra")?.get(*key))?
+                .as_str()
+                .map(|s| s.trim().to_owned())
+        })
+        .unwrap_or_default()
+}
+
+// Bluesky self-labels (ex: "!no-unauthenticated", "graphic-media") from `[extra]`
+fn extract_bsky_labels(content: &str) -> Vec<String> {
+    front_table(content)
+        .and_then(|front| front.get("extra")?.get("bsky_labels")?.as_array().cloned())
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Mastodon's limits for the number of choices and the duration of a poll
+const POLL_OPTIONS: std::ops::RangeInclusive<usize> = 2..=4;
+const POLL_EXPIRY_SECS: std::ops::RangeInclusive<u64> = 300..=2_629_746;
+
+// Poll attached to the toot, from `[extra] social_poll` (ignored by Bluesky)
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Poll {
+    options: Vec<String>,
+    // duration of the poll in seconds
+    expires_in: u64,
+}
+
+fn extract_social_poll(content: &str) -> Result<Option<Poll>> {
+    let Some(poll) =
+        front_table(content).and_then(|front| front.get("extra")?.get("social_poll").cloned())
+    else {
+        return Ok(None);
+    };
+    let poll: Poll = poll.try_into().with_context(|| {
+        "Invalid `social_poll`, expected `{ options = [...], expires_in = <seconds> }`"
+    })?;
+    if !POLL_OPTIONS.contains(&poll.options.len()) {
+        bail!(
+            "`social_poll` must have between {} and {} options",
+            POLL_OPTIONS.start(),
+            POLL_OPTIONS.end()
+        );
+    }
+    if !POLL_EXPIRY_SECS.contains(&poll.expires_in) {
+        bail!(
+            "`social_poll.expires_in` must be between {} and {} seconds",
+            POLL_EXPIRY_SECS.start(),
+            POLL_EXPIRY_SECS.end()
+        );
+    }
+    Ok(Some(poll))
+}
+
+// Image attached to the social posts, from `[extra] social_image`, read once for all the instances
+#[derive(Debug, Clone)]
+pub struct Media {
+    bytes: Vec<u8>,
+    mime: &'static str,
+    file_name: String,
+    // from `[extra] social_image_alt`
+    alt: String,
+    // decoded once, to be downscaled for the instances with a lower size limit
+    image: DynamicImage,
+}
+
+impl Media {
+    // The image as is if it is at most `max_bytes` long, else downscaled as a JPEG until it is
+    fn fit(&self, max_bytes: usize) -> Result<Cow<'_, Media>> {
+        if self.bytes.len() <= max_bytes {
+            return Ok(Cow::Borrowed(self));
+        }
+        let media = self.to_jpeg(max_bytes)?;
+        info!(
+            "Image downscaled from {} to {} bytes to fit the {max_bytes} bytes limit",
+            self.bytes.len(),
+            media.bytes.len()
+        );
+        Ok(Cow::Owned(media))
+    }
+
+    // JPEG of the image at most `max_bytes` long, smaller and smaller until it fits
+    fn to_jpeg(&self, max_bytes: usize) -> Result<Media> {
+        let (width, height) = (self.image.width(), self.image.height());
+        let mut scale = 1.0;
+        loop {
+            let (w, h) = (
+                (f64::from(width) * scale) as u32,
+                (f64::from(height) * scale) as u32,
+            );
+            if w < MIN_IMAGE_SIDE || h < MIN_IMAGE_SIDE {
+                bail!(
+                    "the image is {} bytes and can't be downscaled under the {max_bytes} bytes limit",
+                    self.bytes.len()
+                );
+            }
+            let resized = if scale < 1.0 {
+                self.image.resize(w, h, FilterType::Triangle)
+            } else {
+                self.image.clone()
+            };
+            let mut bytes = Vec::new();
+            JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+                .encode_image(&resized.into_rgb8())?;
+            if bytes.len() <= max_bytes {
+                let stem = Path::new(&self.file_name)
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                return Ok(Media {
+                    bytes,
+                    mime: "image/jpeg",
+                    file_name: format!("{stem}.jpg"),
+                    alt: self.alt.clone(),
+                    image: self.image.clone(),
+                });
+            }
+            scale *= 0.75;
+        }
+    }
+}
+
+fn image_mime(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// `social_image` is relative to the website's directory, or to `static` if it starts with `/`, as
+// in the URL of the image on the site
+fn extract_social_image(content: &str) -> Result<Option<Media>> {
+    let Some(extra) =
+        front_table(content).and_then(|front| front.get("extra")?.as_table().cloned())
+    else {
+        return Ok(None);
+    };
+    let Some(image) = extra.get("social_image").and_then(|image| image.as_str()) else {
+        return Ok(None);
+    };
+    let path = match image.strip_prefix('/') {
+        Some(image) => Path::new("static").join(image),
+        None => PathBuf::from(image),
+    };
+    let Some(mime) = image_mime(&path) else {
+        bail!("`social_image` must be a PNG, JPEG, GIF or WebP image: `{image}`");
+    };
+    let bytes = std::fs::read(&path)
+        .with_context(|| format!("Failed to read `{}`", path.to_string_lossy()))?;
+    let image = image::load_from_memory(&bytes)
+        .with_context(|| format!("Failed to decode `{}`", path.to_string_lossy()))?;
+    Ok(Some(Media {
+        bytes,
+        image,
+        mime,
+        file_name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        alt: extra
+            .get("social_image_alt")
+            .and_then(|alt| alt.as_str())
+            .unwrap_or_default()
+            .to_owned(),
+    }))
+}
+
+// Template of the social post: `template` if given, as is, or `social_template` in the language
+// of the post
+fn toot_template(
     templates_dir: &Path,
+    cfg: &SocialCfg,
+    lang: &Lang,
+    template: Option<&Path>,
+    social_template: &Path,
+) -> Result<String> {
+    match template {
+        Some(template) => std::fs::read_to_string(template)
+            .with_context(|| format!("Failed to read `{}`", template.to_string_lossy())),
+        None => read_template(&templates_dir.join(social_template), cfg, lang),
+    }
+}
+
+// Terms of the post's `tag_taxonomy` as written in its frontmatter, in `[taxonomies]` or at top
+// level
+fn post_tags(content: &str, cfg: &SocialCfg) -> Vec<String> {
+    taxonomy_terms(content, &cfg.tag_taxonomy)
+}
+
+// Terms of `taxonomy` in the post's frontmatter, in `[taxonomies]` or at top level
+pub fn taxonomy_terms(content: &str, taxonomy: &str) -> Vec<String> {
+    let Some(front) = front_table(content) else {
+        return Vec::new();
+    };
+    let tags = front
+        .get("taxonomies")
+        .and_then(|tax| tax.get(taxonomy))
+        .or_else(|| front.get(taxonomy));
+    tags.and_then(|tags| tags.as_array())
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|tag| tag.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// `social_template`, or the template of the first `tag_template` matching a tag of the post
+fn social_template<'a>(content: &str, cfg: &'a SocialCfg) -> &'a Path {
+    let tags = post_tags(content, cfg);
+    cfg.tag_template
+        .iter()
+        .find(|tag_template| tags.contains(&tag_template.tag))
+        .map(|tag_template| tag_template.template.as_path())
+        .unwrap_or(&cfg.social_template)
+}
+
+fn create_toot_content(
+    template: &str,
     dest: &Path,
     cfg: &SocialCfg,
-    title: &Title,
     lang: &Lang,
+    title: &Title,
+    description: &str,
     tags: &TagsList,
 ) -> Result<StatusContent> {
-    let toot_tpl = templates_dir.join(&cfg.social_template);
-
-    let template = read_template(&toot_tpl, cfg, lang)?;
-
     // template filling
     // fill title
     let status = template.replace("{title}", title);
 
+    // fill description
+    let status = status.replace("{description}", description);
+
     // fill link
-    let link = format!(
-        "{}/posts/{}/",
-        cfg.base_url,
-        dest.file_stem()
-            .expect("Should have file_name by now")
-            .to_string_lossy()
-    );
+    let link = post_link(cfg, dest);
     let status = status.replace("{link}", &link);
 
     // fill tags
-    let tags_list = tags.iter().fold(String::new(), |mut res, tag| {
-        res.push('#');
-        res.push_str(tag);
-        res.push(' ');
-        if tag == "rust" {
+    let hashtags: Vec<String> = tags
+        .iter()
+        .flat_map(|tag| {
             // both tags are used for Rust programming language
-            res.push_str("#RustLang ");
+            let rust_lang = (tag == "rust").then(|| "#RustLang".to_owned());
+            std::iter::once(format!("#{tag}")).chain(rust_lang)
+        })
+        .collect();
+    let status = fill_tags(&status, &hashtags.join(" ")).trim().to_owned();
+    // so every social post leads back to the blog, even with a template relying on the preview
+    // card
+    let status = if cfg.always_include_link && !status.contains(&link) {
+        format!("{status}\n\n{link}")
+    } else {
+        status
+    };
+    match cfg.signature_for(lang) {
+        Some(signature) => {
+            let signed = format!("{status}\n\n{}", signature.trim());
+            warn_signature_length(cfg, &status, &signed);
+            Ok(StatusContent(signed))
         }
-        res
-    });
-    Ok(StatusContent(
-        status.replace("{tags}", &tags_list).trim().to_owned(),
-    ))
+        None => Ok(StatusContent(status)),
+    }
+}
+
+// Replace `{tags}` by `hashtags`, with one space between them and the text around, so they don't
+// glue to a word and are not rendered as a single hashtag
+fn fill_tags(status: &str, hashtags: &str) -> String {
+    let mut parts = status.split("{tags}");
+    let mut filled = parts.next().unwrap_or_default().to_owned();
+    for part in parts {
+        if hashtags.is_empty() {
+            if filled.ends_with(' ') && part.starts_with(' ') {
+                filled.pop();
+            }
+        } else {
+            if filled.ends_with(|c: char| !c.is_whitespace()) {
+                filled.push(' ');
+            }
+            filled.push_str(hashtags);
+            if part.starts_with(|c: char| !c.is_whitespace()) {
+                filled.push(' ');
+            }
+        }
+        filled.push_str(part);
+    }
+    filled
+}
+
+// Longest social post accepted, in characters. Mastodon servers can raise it, 500 is the default
+fn max_chars(api: SocialApi) -> usize {
+    match api {
+        SocialApi::Mastodon => 500,
+        SocialApi::Bluesky => 300,
+    }
+}
+
+// The signature must not be what makes the social post refused by a server
+fn warn_signature_length(cfg: &SocialCfg, status: &str, signed: &str) {
+    let (len, signed_len) = (status.chars().count(), signed.chars().count());
+    let apis: BTreeSet<_> = cfg.instances.iter().map(|instance| instance.api).collect();
+    for api in apis {
+        let max = max_chars(api);
+        if len <= max && signed_len > max {
+            warn!(
+                "`social_signature` makes the social post {signed_len} characters long, over \
+                 the {max} characters of {api}"
+            );
+        }
+    }
 }
 
 fn create_toot_link(
@@ -239,43 +766,1293 @@ fn create_toot_link(
     Ok(tpl.replace("{links}", links))
 }
 
-pub async fn push_to_social(cfg: &SocialCfg, content: &str, dest: &Path) -> Result<String> {
+// `{…}` tokens of `template` that are not in `known`, which would be left as is in the social post
+fn unknown_placeholders(template: &str, known: &[&str]) -> Vec<String> {
+    let reg = regex::Regex::new(r"\{([^{}\s]*)\}").unwrap();
+    let mut unknown = Vec::new();
+    for c in reg.captures_iter(template) {
+        let name = c[1].to_owned();
+        if !known.contains(&name.as_str()) && !unknown.contains(&name) {
+            unknown.push(name);
+        }
+    }
+    unknown
+}
+
+// `path` and its variants per language (ex: "social.fr.txt"), among the existing files
+fn template_variants(path: &Path) -> Result<Vec<PathBuf>> {
+    let stem = path
+        .file_stem()
+        .ok_or_else(|| anyhow!("No filename"))?
+        .to_string_lossy();
+    let prefix = format!("{stem}.");
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut variants: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|variant| {
+                variant.file_name().is_some_and(|name| {
+                    let name = name.to_string_lossy();
+                    name.starts_with(&prefix)
+                        && name.ends_with(".txt")
+                        && name.len() > prefix.len() + 4
+                })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    variants.sort();
+    if path.exists() {
+        variants.insert(0, path.to_owned());
+    }
+    Ok(variants)
+}
+
+// Unknown placeholders of each social template, with its language variants: `social_template`,
+// the `tag_template` ones and `link_template`. A template without any file is an error, as
+// publishing would fail
+pub fn check_templates(cfg: &SocialCfg) -> Result<Vec<(PathBuf, Vec<String>)>> {
+    let status_templates = std::iter::once(&cfg.social_template)
+        .chain(
+            cfg.tag_template
+                .iter()
+                .map(|tag_template| &tag_template.template),
+        )
+        .map(|template| (template, STATUS_PLACEHOLDERS.as_slice()));
+    let templates = status_templates.chain(std::iter::once((
+        &cfg.link_template,
+        LINK_PLACEHOLDERS.as_slice(),
+    )));
+
+    let mut checked = Vec::new();
+    for (template, known) in templates {
+        let path = cfg.templates_dir.join(template);
+        let variants = template_variants(&path)?;
+        if variants.is_empty() {
+            bail!("No template found: {}", path.to_string_lossy());
+        }
+        for variant in variants {
+            if checked.iter().any(|(path, _)| path == &variant) {
+                continue;
+            }
+            let content = std::fs::read_to_string(&variant)
+                .with_context(|| format!("Failed to read `{}`", variant.to_string_lossy()))?;
+            let unknown = unknown_placeholders(&content, known);
+            checked.push((variant, unknown));
+        }
+    }
+    Ok(checked)
+}
+
+// Make sure the link put in the social posts leads to the post, to catch a wrong `base_url` or
+// `post_path`. The site may not be deployed yet, so a server that can't be reached is only
+// logged. A redirection is fine, the post is at the end of it.
+async fn check_post_link(client: &Client, cfg: &SocialCfg, link: &str) -> Result<()> {
+    let res = match client.head(link).send().await {
+        Ok(res) => res,
+        Err(err) => {
+            warn!("Failed to check the link to the post `{link}`: {err}");
+            return Ok(());
+        }
+    };
+    let status = res.status();
+    if status.is_success() || status.is_redirection() {
+        return Ok(());
+    }
+    match cfg.check_link {
+        LinkCheck::Abort => bail!(
+            "The link to the post `{link}` answers {status}, check `base_url` and `post_path`"
+        ),
+        _ => {
+            warn!(
+                "The link to the post `{link}` answers {status}, check `base_url` and `post_path`"
+            );
+            Ok(())
+        }
+    }
+}
+
+// Post on all the instances. Returns the post's content with the links to the social posts and
+// their records, and the number of social posts that failed while others succeeded.
+pub async fn push_to_social(
+    client: &Client,
+    cfg: &SocialCfg,
+    content: &str,
+    dest: &Path,
+    lang: Option<&str>,
+    template: Option<&Path>,
+) -> Result<(String, usize)> {
     if cfg.instances.is_empty() {
         bail!("No social servers defined.");
     }
 
+    if cfg.check_link != LinkCheck::Off && !cfg.offline {
+        check_post_link(client, cfg, &post_link(cfg, dest)).await?;
+    }
+
+    let instances: Vec<_> = cfg.instances.iter().collect();
+    let posted = post_on_instances(client, cfg, &instances, content, dest, lang, template).await?;
+    let new_content = inject_links(cfg, content, &posted.language, posted.links)?;
+
+    if posted.records.is_empty() {
+        Ok((new_content, posted.nb_failed))
+    } else {
+        let records = toml::Value::try_from(&posted.records)?.to_string();
+        let new_content = set_extra_field(&new_content, SOCIAL_RECORDS_KEY, Some(&records))?;
+        Ok((new_content, posted.nb_failed))
+    }
+}
+
+// Post on the instances of `api` which don't have a record in the frontmatter yet, for posts
+// published before the instances were configured. Returns `None` if there's nothing to post, else
+// the new content of the post and the number of social posts that failed.
+pub async fn backfill_social(
+    client: &Client,
+    cfg: &SocialCfg,
+    content: &str,
+    dest: &Path,
+    api: SocialApi,
+) -> Result<Option<(String, usize)>> {
+    let front: RecordsFront = toml::from_str(&frontmatter(content)?)?;
+    let extra = front.extra.unwrap_or_default();
+    let mut records = extra.emile_social.unwrap_or_default();
+    let instances: Vec<_> = cfg
+        .instances
+        .iter()
+        .filter(|instance| {
+            instance.api == api
+                && !records
+                    .iter()
+                    .any(|record| record.api == api && record.server == instance.server)
+        })
+        .collect();
+    if instances.is_empty() {
+        return Ok(None);
+    }
+
+    let Posted {
+        links,
+        records: new_records,
+        language,
+        nb_failed,
+    } = post_on_instances(client, cfg, &instances, content, dest, None, None).await?;
+    let new_content = match cfg.link_placement {
+        LinkPlacement::Body
+            if cfg.link_injection == LinkInjection::Replace && !content.contains(&cfg.link_tag) =>
+        {
+            warn!(
+                "No `{}` left in `{}`, links to the new posts are not injected",
+                cfg.link_tag,
+                dest.to_string_lossy()
+            );
+            content.to_owned()
+        }
+        // the snippet would be replaced by one with the links to the new posts only
+        LinkPlacement::Body
+            if cfg.link_injection != LinkInjection::Replace
+                && content.contains(LINKS_START_MARKER) =>
+        {
+            warn!(
+                "Social links already in `{}`, links to the new posts are not injected",
+                dest.to_string_lossy()
+            );
+            content.to_owned()
+        }
+        LinkPlacement::Body => inject_links(cfg, content, &language, links)?,
+        LinkPlacement::Frontmatter => {
+            let mut social_links = extra.social_links.unwrap_or_default();
+            social_links.retain(|link| !links.keys().any(|api| api.to_string() == link.name));
+            let links = links.into_iter().map(|(api, url)| SocialLink {
+                name: api.to_string(),
+                url: url.to_string(),
+            });
+            social_links.extend(links);
+            let social_links = toml::Value::try_from(&social_links)?.to_string();
+            set_extra_field(content, SOCIAL_LINKS_KEY, Some(&social_links))?
+        }
+    };
+
+    records.extend(new_records);
+    let records = toml::Value::try_from(&records)?.to_string();
+    let new_content = set_extra_field(&new_content, SOCIAL_RECORDS_KEY, Some(&records))?;
+    Ok(Some((new_content, nb_failed)))
+}
+
+// Outcome of posting on several instances
+struct Posted {
+    // links to the social posts, sorted by API so the injected links are always in the same order
+    links: BTreeMap<SocialApi, Url>,
+    records: Vec<SocialRecord>,
+    // language of the post
+    language: Lang,
+    // social posts that failed, logged
+    nb_failed: usize,
+}
+
+// Post on `instances` at once, a failing instance doesn't prevent posting on the others, it is
+// counted in `nb_failed`. Fails if no post succeeded.
+async fn post_on_instances(
+    client: &Client,
+    cfg: &SocialCfg,
+    instances: &[&SocialInstance],
+    content: &str,
+    dest: &Path,
+    lang: Option<&str>,
+    template: Option<&Path>,
+) -> Result<Posted> {
     let (title, language, tags) = extract_title_lang_tags(content, cfg)?;
+    let language = lang.map(|lang| Lang(lang.to_owned())).unwrap_or(language);
+
+    let templates_dir = &cfg.templates_dir;
+    let description = extract_description(content);
+    let bsky_labels = extract_bsky_labels(content);
+    let poll = extract_social_poll(content)?;
+    let media = extract_social_image(content)?;
+    // one status per language: the post's one and the ones of the instances posting in several
+    let mut statuses = HashMap::new();
+    let social_template = social_template(content, cfg);
+    let instances_langs = instances.iter().flat_map(|instance| &instance.langs);
+    for lang in std::iter::once(&language.0).chain(instances_langs) {
+        if !statuses.contains_key(lang) {
+            let lang = Lang(lang.clone());
+            let template = toot_template(templates_dir, cfg, &lang, template, social_template)?;
+            let status =
+                create_toot_content(&template, dest, cfg, &lang, &title, &description, &tags)?;
+            statuses.insert(lang.0, status);
+        }
+    }
+    let slug = dest.file_stem().unwrap_or_default().to_string_lossy();
+    let (statuses, language, slug) = (&statuses, &language, &slug);
+    let extras = StatusExtras {
+        bsky_labels: &bsky_labels,
+        poll: poll.as_ref(),
+        media: media.as_ref(),
+        idempotency_src: None,
+    };
+    let posts = join_all(instances.iter().map(|instance| async move {
+        let mut posts = Vec::new();
+        // posted one after the other so the primary language is always the first one
+        for (i, lang) in instance_langs(instance, language).into_iter().enumerate() {
+            let status = &statuses[&lang.0];
+            let idempotency_src = match cfg.idempotency_key {
+                IdempotencyKey::Text => None,
+                IdempotencyKey::Slug => Some(format!("{slug}/{}", lang.0)),
+            };
+            let extras = StatusExtras {
+                // a poll only makes sense once
+                poll: extras.poll.filter(|_| i == 0),
+                idempotency_src: idempotency_src.as_deref(),
+                ..extras
+            };
+            posts.push(push_to_instance(cfg, client, instance, status, &lang, &extras).await);
+        }
+        (instance, posts)
+    }))
+    .await;
+
+    let mut links = BTreeMap::<SocialApi, Url>::new();
+    let mut records = Vec::new();
+    let mut nb_failed = 0;
+    let mut last_err = None;
+    for (instance, posts) in posts {
+        for post in posts {
+            match post {
+                Ok(Some(post)) => {
+                    // the primary language post, or the next one if it failed
+                    links.entry(instance.api).or_insert(post.url);
+                    records.push(SocialRecord {
+                        api: instance.api,
+                        server: instance.server.clone(),
+                        id: post.id,
+                    });
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!(
+                        "Failed to post on {} `{}`: {err}",
+                        instance.api, instance.server
+                    );
+                    nb_failed += 1;
+                    last_err = Some(err);
+                }
+            }
+        }
+    }
+
+    if links.is_empty() {
+        if let Some(err) = last_err {
+            return Err(err);
+        }
+    }
+    Ok(Posted {
+        links,
+        records,
+        language: language.clone(),
+        nb_failed,
+    })
+}
 
-    let templates_dir = PathBuf::from("./templates/");
-    let status = create_toot_content(&templates_dir, dest, cfg, &title, &language, &tags)?;
-    let mut links = HashMap::<SocialApi, Url>::new();
+// Languages to post in on `instance`, the primary one first: the post's language if the instance
+// posts in it, else the first of its `langs`
+fn instance_langs(instance: &SocialInstance, language: &Lang) -> Vec<Lang> {
+    if instance.langs.is_empty() {
+        return vec![language.clone()];
+    }
+    let mut langs: Vec<_> = instance.langs.iter().map(|l| Lang(l.clone())).collect();
+    if let Some(pos) = langs.iter().position(|l| l.0 == language.0) {
+        let primary = langs.remove(pos);
+        langs.insert(0, primary);
+    }
+    langs
+}
+
+// What is posted along the text of a status, not all of it is supported by every API
+#[derive(Default, Clone, Copy)]
+struct StatusExtras<'a> {
+    bsky_labels: &'a [String],
+    poll: Option<&'a Poll>,
+    media: Option<&'a Media>,
+    // what Mastodon's Idempotency-Key is derived from, the text of the status if not set
+    idempotency_src: Option<&'a str>,
+}
+
+async fn push_to_instance(
+    cfg: &SocialCfg,
+    client: &Client,
+    instance: &SocialInstance,
+    status: &StatusContent,
+    language: &Lang,
+    extras: &StatusExtras<'_>,
+) -> Result<Option<SocialPost>> {
+    if cfg.offline {
+        info!(
+            "Offline: would post on {} `{}` in `{}`:\n{}",
+            instance.api,
+            instance.server,
+            language.0,
+            status.as_str()
+        );
+        return Ok(None);
+    }
+    let StatusExtras {
+        bsky_labels,
+        poll,
+        media,
+        idempotency_src,
+    } = *extras;
+    match instance.api {
+        SocialApi::Mastodon => {
+            push_to_mastodon(
+                client,
+                instance,
+                status,
+                language,
+                poll,
+                media,
+                idempotency_src,
+            )
+            .await
+        }
+        SocialApi::Bluesky => {
+            push_to_bsky(client, instance, status, language, bsky_labels, media).await
+        }
+    }
+}
+
+// Post `text` as is on all the instances, without a blog post behind it. Returns the URL of the
+// post on each instance it succeeded on, and the number of instances it failed on.
+pub async fn post_status<'a>(
+    client: &Client,
+    cfg: &'a SocialCfg,
+    text: &str,
+    lang: Option<&str>,
+) -> Result<(Vec<(&'a SocialInstance, Url)>, usize)> {
+    if cfg.instances.is_empty() {
+        bail!("No social servers defined.");
+    }
+
+    let status = &StatusContent(text.to_owned());
+    let language = &Lang(lang.unwrap_or(&cfg.default_lang).to_owned());
+    let posts = join_all(cfg.instances.iter().map(|instance| async move {
+        let extras = StatusExtras::default();
+        let post = push_to_instance(cfg, client, instance, status, language, &extras).await;
+        (instance, post)
+    }))
+    .await;
+
+    let mut urls = Vec::new();
+    let mut nb_failed = 0;
+    let mut last_err = None;
+    for (instance, post) in posts {
+        match post {
+            Ok(Some(post)) => urls.push((instance, post.url)),
+            Ok(None) => {}
+            Err(err) => {
+                error!(
+                    "Failed to post on {} `{}`: {err}",
+                    instance.api, instance.server
+                );
+                nb_failed += 1;
+                last_err = Some(err);
+            }
+        }
+    }
+    match last_err {
+        Some(err) if urls.is_empty() => Err(err),
+        _ => Ok((urls, nb_failed)),
+    }
+}
 
-    for instance in &cfg.instances {
-        let url = match instance.api {
-            SocialApi::Mastodon => push_to_mastodon(instance, &status, &language).await?,
-            SocialApi::Bluesky => push_to_bsky(instance, &status, &language).await?,
+// Log in each instance without posting, giving the account each token belongs to, or why it
+// doesn't work
+pub async fn check_tokens<'a>(
+    client: &Client,
+    cfg: &'a SocialCfg,
+) -> Result<Vec<(&'a SocialInstance, Result<String>)>> {
+    if cfg.offline {
+        bail!("Offline: the tokens can't be checked without contacting the social servers");
+    }
+    Ok(join_all(cfg.instances.iter().map(|instance| async move {
+        let account = match instance.api {
+            SocialApi::Mastodon => verify_mastodon(client, instance).await,
+            SocialApi::Bluesky => verify_bsky(client, instance).await,
         };
-        if let Some(url) = url {
-            links.insert(instance.api, url);
+        (instance, account)
+    }))
+    .await)
+}
+
+// Put the links to the social posts in the blog post, where `link_placement` says
+fn inject_links(
+    cfg: &SocialCfg,
+    content: &str,
+    language: &Lang,
+    links: BTreeMap<SocialApi, Url>,
+) -> Result<String> {
+    if cfg.offline {
+        // nothing was posted, the link tag is kept for the real publication
+        return Ok(content.to_owned());
+    }
+    let new_content = match cfg.link_placement {
+        LinkPlacement::Body => {
+            let links = links
+                .into_iter()
+                .fold(String::new(), |mut acc, (api, url)| {
+                    if !acc.is_empty() {
+                        acc.push_str(", ");
+                    }
+                    acc.push_str(&format!("[{api}]({url})"));
+                    acc
+                });
+
+            info!("Inject social links: {links:?}");
+
+            let snippet = create_toot_link(&cfg.templates_dir, cfg, language, &links)?;
+            place_links(cfg, content, &snippet)
         }
+        LinkPlacement::Frontmatter => {
+            let links: Vec<SocialLink> = links
+                .into_iter()
+                .map(|(api, url)| SocialLink {
+                    name: api.to_string(),
+                    url: url.to_string(),
+                })
+                .collect();
+
+            info!("Inject social links in frontmatter: {links:?}");
+
+            let content = content.replace(&cfg.link_tag, "");
+            if links.is_empty() {
+                content
+            } else {
+                let links = toml::Value::try_from(&links)?.to_string();
+                set_extra_field(&content, SOCIAL_LINKS_KEY, Some(&links))?
+            }
+        }
+    };
+    Ok(new_content)
+}
+
+// Put the links snippet in the body, where `link_injection` says. Appended or prepended, it is
+// between markers, replaced by the new snippet when the post is published again
+fn place_links(cfg: &SocialCfg, content: &str, snippet: &str) -> String {
+    if cfg.link_injection == LinkInjection::Replace {
+        return content.replace(&cfg.link_tag, snippet);
     }
 
-    let links = links
-        .into_iter()
-        .fold(String::new(), |mut acc, (api, url)| {
-            if !acc.is_empty() {
-                acc.push_str(", ");
+    let content = content.replace(&cfg.link_tag, "");
+    let block = format!(
+        "{LINKS_START_MARKER}\n{}\n{LINKS_END_MARKER}",
+        snippet.trim()
+    );
+    if let Some(start) = content.find(LINKS_START_MARKER) {
+        if let Some(end) = content[start..].find(LINKS_END_MARKER) {
+            let end = start + end + LINKS_END_MARKER.len();
+            return format!("{}{block}{}", &content[..start], &content[end..]);
+        }
+    }
+    match cfg.link_injection {
+        LinkInjection::Prepend => {
+            let (front, body) = content.split_at(body_start(&content));
+            format!("{front}{block}\n{body}")
+        }
+        _ => format!("{}\n\n{block}\n", content.trim_end()),
+    }
+}
+
+// Offset of the body of the post, after the line closing the frontmatter
+fn body_start(content: &str) -> usize {
+    let mut offset = 0;
+    let mut nb_sep = 0;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        if is_delimiter(line) {
+            nb_sep += 1;
+            if nb_sep >= 2 {
+                return offset;
             }
-            acc.push_str(&format!("[{api}]({url})"));
-            acc
+        }
+    }
+    0
+}
+
+// Delete the social posts recorded in the post's frontmatter. Returns the post's content without
+// the records of the deleted posts and the number of posts that failed to be deleted.
+pub async fn retract_from_social(
+    client: &Client,
+    cfg: &SocialCfg,
+    content: &str,
+) -> Result<(String, usize)> {
+    let front: RecordsFront = toml::from_str(&frontmatter(content)?)?;
+    let records = front
+        .extra
+        .and_then(|extra| extra.emile_social)
+        .unwrap_or_default();
+    if records.is_empty() {
+        bail!("No social post recorded in the frontmatter");
+    }
+    if cfg.offline {
+        for record in &records {
+            info!(
+                "Offline: would delete {} post `{}` on `{}`",
+                record.api, record.id, record.server
+            );
+        }
+        return Ok((content.to_owned(), 0));
+    }
+
+    let mut remaining = Vec::new();
+    for record in records {
+        let Some(instance) = cfg
+            .instances
+            .iter()
+            .find(|instance| instance.api == record.api && instance.server == record.server)
+        else {
+            error!(
+                "No {} instance `{}` configured, can't delete `{}`",
+                record.api, record.server, record.id
+            );
+            remaining.push(record);
+            continue;
+        };
+
+        let res = match record.api {
+            SocialApi::Mastodon => delete_from_mastodon(client, instance, &record.id).await,
+            SocialApi::Bluesky => delete_from_bsky(client, instance, &record.id).await,
+        };
+        match res {
+            Ok(_) => info!(
+                "Deleted {} post `{}` on `{}`",
+                record.api, record.id, record.server
+            ),
+            Err(err) => {
+                error!(
+                    "Failed to delete {} post `{}` on `{}`: {err}",
+                    record.api, record.id, record.server
+                );
+                remaining.push(record);
+            }
+        }
+    }
+
+    let nb_failed = remaining.len();
+    let records = if remaining.is_empty() {
+        None
+    } else {
+        Some(toml::Value::try_from(&remaining)?.to_string())
+    };
+    Ok((
+        set_extra_field(content, SOCIAL_RECORDS_KEY, records.as_deref())?,
+        nb_failed,
+    ))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        borrow::Cow,
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        config::{
+            IdempotencyKey, LinkCheck, LinkInjection, LinkPlacement, SectionPath, SocialApi,
+            SocialCfg, SocialInstance, TagCase, TagLang, TagTemplate,
+        },
+        post::{frontmatter, set_extra_field},
+        test_dir,
+    };
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{
+        backfill_social, check_post_link, create_toot_content, extract_description,
+        extract_social_poll, extract_title_lang_tags, format_tag, http_client, instance_langs,
+        place_links, post_link, post_status, push_to_social, retract_from_social, social_template,
+        unknown_placeholders, Client, Lang, Media, RecordsFront, SocialRecord, TagsList,
+        SOCIAL_RECORDS_KEY, STATUS_PLACEHOLDERS,
+    };
+
+    // PNG of `side` pixels wide and high, of noise so it doesn't compress much
+    pub fn png_media(side: u32) -> Media {
+        let mut seed = 42u32;
+        let image = image::RgbImage::from_fn(side, side, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_be_bytes();
+            image::Rgb([r, g, b])
         });
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        Media {
+            bytes: bytes.into_inner(),
+            mime: "image/png",
+            file_name: "hero.png".to_string(),
+            alt: "A hero".to_string(),
+            image: image.into(),
+        }
+    }
 
-    info!("Inject social links: {links:?}");
+    #[test]
+    fn test_media_fit() {
+        let media = png_media(256);
+        let len = media.bytes.len();
+        assert!(matches!(media.fit(len).unwrap(), Cow::Borrowed(_)));
 
-    let new_content = content.replace(
-        &cfg.link_tag,
-        &create_toot_link(&templates_dir, cfg, &language, &links)?,
-    );
+        let fitted = media.fit(len / 8).unwrap();
+        assert!(fitted.bytes.len() <= len / 8);
+        assert_eq!(fitted.mime, "image/jpeg");
+        assert_eq!(fitted.file_name, "hero.jpg");
+        let decoded = image::load_from_memory(&fitted.bytes).unwrap();
+        assert!(decoded.width() < 256);
 
-    Ok(new_content)
+        let err = media.fit(100).unwrap_err();
+        assert!(err.to_string().contains("can't be downscaled"), "{err}");
+    }
+
+    pub fn social_cfg(tag_case: TagCase) -> SocialCfg {
+        SocialCfg {
+            social_template: PathBuf::from("social.txt"),
+            templates_dir: PathBuf::from("./templates/"),
+            default_lang: "en".to_string(),
+            base_url: "https://example.com".to_string(),
+            post_path: "posts".to_string(),
+            section_path: None,
+            tag_taxonomy: "tags".to_string(),
+            tag_lang: None,
+            tag_template: Vec::new(),
+            lang_key: None,
+            filtered_tag: vec!["misc".to_string()],
+            tag_case,
+            link_template: PathBuf::from("social_link.txt"),
+            link_tag: "{$ emile_social $}".to_string(),
+            link_placement: LinkPlacement::Body,
+            link_injection: LinkInjection::Replace,
+            always_include_link: false,
+            social_signature: None,
+            lang_social_signature: HashMap::new(),
+            check_link: LinkCheck::Off,
+            idempotency_key: IdempotencyKey::Text,
+            http_proxy: None,
+            extra_ca_cert: None,
+            instances: Vec::new(),
+            offline: false,
+        }
+    }
+
+    fn tags_with(tag_case: TagCase) -> Vec<String> {
+        let content =
+            "+++\ntitle = \"Post\"\ntags = [\"Machine learning\", \"misc\", \"rust\"]\n+++\n";
+        let (_, _, tags) = extract_title_lang_tags(content, &social_cfg(tag_case)).unwrap();
+        tags.0
+    }
+
+    #[test]
+    fn test_tag_taxonomy() {
+        let mut cfg = social_cfg(TagCase::Lower);
+        cfg.tag_taxonomy = "categories".to_string();
+        let content = "+++\ntitle = \"Post\"\n[taxonomies]\ntags = [\"ignored\"]\ncategories = [\"Rust\", \"misc\"]\n+++\n";
+        let (_, _, tags) = extract_title_lang_tags(content, &cfg).unwrap();
+        assert_eq!(tags.0, ["rust"]);
+    }
+
+    #[test]
+    fn test_social_template() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.tag_template = vec![
+            TagTemplate {
+                tag: "release".to_string(),
+                template: PathBuf::from("social_release.txt"),
+            },
+            TagTemplate {
+                tag: "rust".to_string(),
+                template: PathBuf::from("social_rust.txt"),
+            },
+        ];
+
+        let content = "+++\ntitle = \"Post\"\n[taxonomies]\ntags = [\"rust\", \"release\"]\n+++\n";
+        assert_eq!(
+            social_template(content, &cfg),
+            Path::new("social_release.txt")
+        );
+        let content = "+++\ntitle = \"Post\"\ntags = [\"rust\"]\n+++\n";
+        assert_eq!(social_template(content, &cfg), Path::new("social_rust.txt"));
+        let content = "+++\ntitle = \"Post\"\ntags = [\"misc\"]\n+++\n";
+        assert_eq!(social_template(content, &cfg), cfg.social_template);
+    }
+
+    #[test]
+    fn test_lang() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.tag_lang = Some(vec![TagLang {
+            tag: "english".to_string(),
+            lang: "en".to_string(),
+        }]);
+        cfg.default_lang = "de".to_string();
+
+        let no_tags = "+++\ntitle = \"Post\"\n+++\n";
+        let (_, lang, _) = extract_title_lang_tags(no_tags, &cfg).unwrap();
+        assert_eq!(lang.as_str(), "de");
+
+        let content = "+++\ntitle = \"Post\"\n[taxonomies]\ntags = [\"english\"]\n[extra]\nlang = \"fr\"\n+++\n";
+        let (_, lang, _) = extract_title_lang_tags(content, &cfg).unwrap();
+        assert_eq!(lang.as_str(), "en");
+
+        cfg.lang_key = Some("extra.lang".to_string());
+        let (_, lang, _) = extract_title_lang_tags(content, &cfg).unwrap();
+        assert_eq!(lang.as_str(), "fr");
+    }
+
+    #[test]
+    fn test_title_with_equal_sign() {
+        let content = "+++\ntitle = \"A = B explained\"\n+++\n";
+        let (title, _, _) = extract_title_lang_tags(content, &social_cfg(TagCase::Pascal)).unwrap();
+        assert_eq!(title.as_str(), "A = B explained");
+    }
+
+    #[test]
+    fn test_tag_case_pascal() {
+        assert_eq!(tags_with(TagCase::Pascal), vec!["MachineLearning", "Rust"]);
+    }
+
+    #[test]
+    fn test_tag_case_camel() {
+        assert_eq!(tags_with(TagCase::Camel), vec!["machineLearning", "rust"]);
+    }
+
+    #[test]
+    fn test_tag_case_lower() {
+        assert_eq!(tags_with(TagCase::Lower), vec!["machinelearning", "rust"]);
+    }
+
+    #[test]
+    fn test_tag_case_original() {
+        assert_eq!(
+            tags_with(TagCase::Original),
+            vec!["Machinelearning", "rust"]
+        );
+        assert_eq!(format_tag("C'est l'été", TagCase::Original), "Cestlété");
+        assert_eq!(format_tag("rust-lang", TagCase::Original), "rustlang");
+    }
+
+    #[test]
+    fn test_tag_punctuation_only() {
+        for case in [
+            TagCase::Pascal,
+            TagCase::Camel,
+            TagCase::Lower,
+            TagCase::Original,
+        ] {
+            assert_eq!(format_tag("!!!", case), "");
+            assert_eq!(format_tag("-rust--lang-", case).to_lowercase(), "rustlang");
+        }
+        let content = "+++\ntitle = \"Post\"\ntags = [\"!!!\", \"rust\"]\n+++\n";
+        let (_, _, tags) = extract_title_lang_tags(content, &social_cfg(TagCase::Pascal)).unwrap();
+        assert_eq!(tags.0, vec!["Rust"]);
+    }
+
+    #[test]
+    fn test_post_link_prefix() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        let dest = Path::new("content/articles/my-post.md");
+        assert_eq!(post_link(&cfg, dest), "https://example.com/posts/my-post/");
+
+        cfg.post_path = "articles".to_string();
+        assert_eq!(
+            post_link(&cfg, dest),
+            "https://example.com/articles/my-post/"
+        );
+
+        cfg.section_path = Some(vec![SectionPath {
+            dir: PathBuf::from("content/notes"),
+            path: "notes".to_string(),
+        }]);
+        assert_eq!(
+            post_link(&cfg, Path::new("content/notes/my-note.md")),
+            "https://example.com/notes/my-note/"
+        );
+    }
+
+    #[test]
+    fn test_social_records_round_trip() {
+        let records = vec![
+            SocialRecord {
+                api: SocialApi::Mastodon,
+                server: "mastodon.social".to_string(),
+                id: "1234".to_string(),
+            },
+            SocialRecord {
+                api: SocialApi::Bluesky,
+                server: "bsky.social".to_string(),
+                id: "at://did:plc:abc/app.bsky.feed.post/xyz".to_string(),
+            },
+        ];
+        let value = toml::Value::try_from(&records).unwrap().to_string();
+        let content = "+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+02:00\n+++\nbody\n";
+        let content = set_extra_field(content, SOCIAL_RECORDS_KEY, Some(&value)).unwrap();
+
+        let front: RecordsFront = toml::from_str(&frontmatter(&content).unwrap()).unwrap();
+        let parsed = front.extra.unwrap().emile_social.unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].api, SocialApi::Mastodon);
+        assert_eq!(parsed[0].id, "1234");
+        assert_eq!(parsed[1].server, "bsky.social");
+    }
+
+    #[tokio::test]
+    async fn test_backfill_skips_recorded_instances() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.instances = vec![SocialInstance {
+            server: "bsky.social".to_string(),
+            api: SocialApi::Bluesky,
+            token_var: "BSKY_PWD".to_string(),
+            token_file: None,
+            handle_var: Some("BSKY_HANDLE".to_string()),
+            langs: Vec::new(),
+            max_retries: 0,
+            pds_host: None,
+            app_url: None,
+        }];
+        let content = "+++\ntitle = \"Post\"\n[extra]\nemile_social = [{ api = \"Bluesky\", server = \"bsky.social\", id = \"at://xyz\" }]\n+++\nbody\n";
+        let dest = Path::new("content/posts/post.md");
+
+        let backfilled =
+            backfill_social(&Client::new(), &cfg, content, dest, SocialApi::Bluesky).await;
+        assert!(backfilled.unwrap().is_none());
+        let backfilled =
+            backfill_social(&Client::new(), &cfg, content, dest, SocialApi::Mastodon).await;
+        assert!(backfilled.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_social_poll() {
+        assert!(extract_social_poll("+++\ntitle = \"Post\"\n+++\n")
+            .unwrap()
+            .is_none());
+
+        let content = "+++\n[extra]\nsocial_poll = { options = [\"Yes\", \"No\"], expires_in = 86400 }\n+++\n";
+        let poll = extract_social_poll(content).unwrap().unwrap();
+        assert_eq!(poll.options, ["Yes", "No"]);
+        assert_eq!(poll.expires_in, 86400);
+
+        let one_option =
+            "+++\n[extra]\nsocial_poll = { options = [\"Yes\"], expires_in = 86400 }\n+++\n";
+        assert!(extract_social_poll(one_option).is_err());
+        let too_short =
+            "+++\n[extra]\nsocial_poll = { options = [\"Yes\", \"No\"], expires_in = 10 }\n+++\n";
+        assert!(extract_social_poll(too_short).is_err());
+    }
+
+    #[test]
+    fn test_description_substitution() {
+        let cfg = social_cfg(TagCase::Pascal);
+        let content = "+++\ntitle = \"Post\"\ndescription = \"All about posts\"\ntags = []\n+++\n";
+        let (title, _, tags) = extract_title_lang_tags(content, &cfg).unwrap();
+        let description = extract_description(content);
+        let status = create_toot_content(
+            "{title}: {description}",
+            Path::new("content/posts/post.md"),
+            &cfg,
+            &Lang("en".to_string()),
+            &title,
+            &description,
+            &tags,
+        )
+        .unwrap();
+        assert_eq!(status.as_str(), "Post: All about posts");
+
+        let content = "+++\ntitle = \"Post\"\n[extra]\nsummary = \"A summary\"\n+++\n";
+        assert_eq!(extract_description(content), "A summary");
+        assert_eq!(extract_description("+++\ntitle = \"Post\"\n+++\n"), "");
+    }
+
+    #[test]
+    fn test_always_include_link() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        let content = "+++\ntitle = \"Post\"\ntags = []\n+++\n";
+        let (title, _, tags) = extract_title_lang_tags(content, &cfg).unwrap();
+        let dest = Path::new("content/posts/post.md");
+        let status = |template: &str, cfg: &SocialCfg| {
+            create_toot_content(
+                template,
+                dest,
+                cfg,
+                &Lang("en".to_string()),
+                &title,
+                "",
+                &tags,
+            )
+            .unwrap()
+            .as_str()
+            .to_owned()
+        };
+        assert_eq!(status("New: {title}", &cfg), "New: Post");
+        cfg.always_include_link = true;
+        let link = post_link(&cfg, dest);
+        assert_eq!(status("New: {title}", &cfg), format!("New: Post\n\n{link}"));
+        // not twice
+        assert_eq!(
+            status("New: {title} {link}", &cfg),
+            format!("New: Post {link}")
+        );
+    }
+
+    #[test]
+    fn test_unknown_placeholders() {
+        assert!(unknown_placeholders("{title} {link}\n{tags}", &STATUS_PLACEHOLDERS).is_empty());
+        assert_eq!(
+            unknown_placeholders("{titel}: {link} {summary} {titel}", &STATUS_PLACEHOLDERS),
+            ["titel", "summary"]
+        );
+
+        let tmp = test_dir();
+        let dir = tmp.path();
+        std::fs::write(dir.join("social.txt"), "{title} {link}").unwrap();
+        std::fs::write(dir.join("social.fr.txt"), "{titre} {link}").unwrap();
+        std::fs::write(dir.join("social_link.txt"), "{links}").unwrap();
+        let mut cfg = social_cfg(TagCase::Lower);
+        cfg.templates_dir = dir.to_path_buf();
+
+        let checked = super::check_templates(&cfg).unwrap();
+        assert_eq!(
+            checked,
+            [
+                (dir.join("social.txt"), Vec::new()),
+                (dir.join("social.fr.txt"), vec!["titre".to_string()]),
+                (dir.join("social_link.txt"), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_place_links() {
+        let mut cfg = social_cfg(TagCase::Lower);
+        let content = "+++\ntitle = \"Post\"\n+++\n\nHello\n\n{$ emile_social $}\n";
+        assert_eq!(
+            place_links(&cfg, content, "[Mastodon](url)"),
+            "+++\ntitle = \"Post\"\n+++\n\nHello\n\n[Mastodon](url)\n"
+        );
+
+        let block = "<!-- emile_social -->\n[Mastodon](url)\n<!-- /emile_social -->";
+        cfg.link_injection = LinkInjection::Append;
+        let appended = place_links(&cfg, content, "[Mastodon](url)\n");
+        assert_eq!(
+            appended,
+            format!("+++\ntitle = \"Post\"\n+++\n\nHello\n\n{block}\n")
+        );
+        // published again, the links are replaced
+        assert_eq!(
+            place_links(&cfg, &appended, "[Mastodon](new_url)"),
+            appended.replace("(url)", "(new_url)")
+        );
+
+        cfg.link_injection = LinkInjection::Prepend;
+        let prepended = place_links(
+            &cfg,
+            "+++\ntitle = \"Post\"\n+++\n\nHello\n",
+            "[Mastodon](url)",
+        );
+        assert_eq!(
+            prepended,
+            format!("+++\ntitle = \"Post\"\n+++\n{block}\n\nHello\n")
+        );
+        assert_eq!(place_links(&cfg, &prepended, "[Mastodon](url)"), prepended);
+    }
+
+    #[test]
+    fn test_tags_spacing() {
+        let cfg = social_cfg(TagCase::Lower);
+        let content = "+++\ntitle = \"Post\"\ntags = [\"rust\", \"zola\"]\n+++\n";
+        let (title, _, tags) = extract_title_lang_tags(content, &cfg).unwrap();
+        let dest = Path::new("content/posts/post.md");
+        let status = |template: &str, tags| {
+            create_toot_content(
+                template,
+                dest,
+                &cfg,
+                &Lang("en".to_string()),
+                &title,
+                "",
+                tags,
+            )
+            .unwrap()
+            .as_str()
+            .to_owned()
+        };
+        assert_eq!(
+            status("New: {title}{tags}!", &tags),
+            "New: Post #rust #RustLang #zola !"
+        );
+        assert_eq!(
+            status("{title}\n{tags}", &tags),
+            "Post\n#rust #RustLang #zola"
+        );
+        let no_tags = TagsList(Vec::new());
+        assert_eq!(status("New: {title} {tags} now", &no_tags), "New: Post now");
+    }
+
+    #[test]
+    fn test_social_signature() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.always_include_link = true;
+        cfg.social_signature = Some("— via my blog".to_string());
+        cfg.lang_social_signature =
+            HashMap::from([("fr".to_string(), "— via mon blog".to_string())]);
+        let content = "+++\ntitle = \"Post\"\ntags = []\n+++\n";
+        let (title, _, tags) = extract_title_lang_tags(content, &cfg).unwrap();
+        let dest = Path::new("content/posts/post.md");
+        let link = post_link(&cfg, dest);
+        let status = |lang: &str, cfg: &SocialCfg| {
+            create_toot_content(
+                "{title}",
+                dest,
+                cfg,
+                &Lang(lang.to_string()),
+                &title,
+                "",
+                &tags,
+            )
+            .unwrap()
+            .as_str()
+            .to_owned()
+        };
+        assert_eq!(
+            status("en", &cfg),
+            format!("Post\n\n{link}\n\n— via my blog")
+        );
+        assert_eq!(
+            status("fr", &cfg),
+            format!("Post\n\n{link}\n\n— via mon blog")
+        );
+        cfg.social_signature = None;
+        assert_eq!(status("en", &cfg), format!("Post\n\n{link}"));
+    }
+
+    #[test]
+    fn test_instance_langs() {
+        let langs = |langs: &[&str], post_lang: &str| -> Vec<String> {
+            let instance = SocialInstance {
+                server: "mastodon.social".to_string(),
+                api: SocialApi::Mastodon,
+                token_var: "TOKEN".to_string(),
+                token_file: None,
+                handle_var: None,
+                langs: langs.iter().map(|l| l.to_string()).collect(),
+                max_retries: 0,
+                pds_host: None,
+                app_url: None,
+            };
+            instance_langs(&instance, &Lang(post_lang.to_string()))
+                .into_iter()
+                .map(|l| l.0)
+                .collect()
+        };
+        assert_eq!(langs(&[], "fr"), ["fr"]);
+        assert_eq!(langs(&["en", "fr"], "fr"), ["fr", "en"]);
+        assert_eq!(langs(&["en", "fr"], "de"), ["en", "fr"]);
+    }
+
+    #[tokio::test]
+    async fn test_check_post_link() {
+        let server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/posts/post/"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.check_link = LinkCheck::Abort;
+        let link = |path: &str| format!("{}{path}", server.uri());
+        let client = http_client(&cfg).unwrap();
+
+        assert!(check_post_link(&client, &cfg, &link("/posts/post/"))
+            .await
+            .is_ok());
+        let err = check_post_link(&client, &cfg, &link("/post/"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{err}");
+        cfg.check_link = LinkCheck::Warn;
+        assert!(check_post_link(&client, &cfg, &link("/post/"))
+            .await
+            .is_ok());
+        // not deployed yet
+        cfg.check_link = LinkCheck::Abort;
+        assert!(
+            check_post_link(&client, &cfg, "http://127.0.0.1:9/posts/post/")
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_partial_failure() {
+        let (ok_server, failing_server) = (MockServer::start().await, MockServer::start().await);
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "42",
+                "uri": "https://mastodon.example/users/me/statuses/42",
+            })))
+            .mount(&ok_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/statuses/42/bookmark"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&ok_server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("Something went wrong"))
+            .mount(&failing_server)
+            .await;
+
+        std::env::set_var("EMILE_TEST_PARTIAL_TOKEN", "token");
+        let mut cfg = social_cfg(TagCase::Lower);
+        cfg.link_placement = LinkPlacement::Frontmatter;
+        for server in [&ok_server, &failing_server] {
+            cfg.instances.push(SocialInstance {
+                server: server.uri(),
+                api: SocialApi::Mastodon,
+                token_var: "EMILE_TEST_PARTIAL_TOKEN".to_string(),
+                token_file: None,
+                handle_var: None,
+                langs: Vec::new(),
+                max_retries: 0,
+                pds_host: None,
+                app_url: None,
+            });
+        }
+        let dir = test_dir();
+        let template = dir.path().join("social.txt");
+        std::fs::write(&template, "{title} {link}").unwrap();
+        let content = "+++\ntitle = \"Post\"\n+++\nbody\n";
+
+        let (new_content, nb_failed) = push_to_social(
+            &Client::new(),
+            &cfg,
+            content,
+            Path::new("content/posts/post.md"),
+            None,
+            Some(&template),
+        )
+        .await
+        .unwrap();
+        // the failure is reported, the successful post is recorded
+        assert_eq!(nb_failed, 1);
+        let front: RecordsFront = toml::from_str(&frontmatter(&new_content).unwrap()).unwrap();
+        let records = front.extra.unwrap().emile_social.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].server, ok_server.uri());
+    }
+
+    #[test]
+    fn test_http_client() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        assert!(http_client(&cfg).is_ok());
+        // built from the config it is given, not the one of a previous call
+        cfg.extra_ca_cert = Some(PathBuf::from("emile_test_missing_ca.pem"));
+        let err = http_client(&cfg).unwrap_err();
+        assert!(
+            err.to_string().contains("emile_test_missing_ca.pem"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_offline() {
+        let mut cfg = social_cfg(TagCase::Pascal);
+        cfg.instances.push(SocialInstance {
+            // nothing listens there, any request would fail
+            server: "http://127.0.0.1:9".to_string(),
+            api: SocialApi::Mastodon,
+            token_var: "EMILE_TEST_OFFLINE_TOKEN".to_string(),
+            token_file: None,
+            handle_var: None,
+            langs: Vec::new(),
+            max_retries: 0,
+            pds_host: None,
+            app_url: None,
+        });
+        cfg.offline = true;
+        assert!(post_status(&Client::new(), &cfg, "Hello", None)
+            .await
+            .unwrap()
+            .0
+            .is_empty());
+
+        let dir = test_dir();
+        let template = dir.path().join("social.txt");
+        std::fs::write(&template, "{title} {link}").unwrap();
+        let content = "+++\ntitle = \"Post\"\n+++\nbody\n{$ emile_social $}\n";
+        let (new_content, nb_failed) = push_to_social(
+            &Client::new(),
+            &cfg,
+            content,
+            Path::new("content/posts/post.md"),
+            None,
+            Some(&template),
+        )
+        .await
+        .unwrap();
+        // the link tag is left for the real publication
+        assert_eq!((new_content.as_str(), nb_failed), (content, 0));
+
+        let content = "+++\ntitle = \"Post\"\n[extra]\nemile_social = [{ api = \"Mastodon\", \
+                       server = \"http://127.0.0.1:9\", id = \"1\" }]\n+++\n";
+        let (new_content, nb_failed) = retract_from_social(&Client::new(), &cfg, content)
+            .await
+            .unwrap();
+        // nothing deleted, the records are kept
+        assert_eq!((new_content.as_str(), nb_failed), (content, 0));
+    }
 }
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{config::SiteConfig, format_date, watcher::SiteWatcher};
+
+// written in the website's directory by the `watch` command
+pub const STATUS_FILE: &str = ".emile-status.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct QueuedPost {
+    date: String,
+    path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Status {
+    updated_at: String,
+    next: Option<String>,
+    queued: Vec<QueuedPost>,
+}
+
+pub fn write_status(watcher: &SiteWatcher, cfg: &SiteConfig) -> Result<()> {
+    let queued: Vec<QueuedPost> = match watcher.scheduled.lock() {
+        Ok(scheduled) => scheduled
+            .iter()
+            .flat_map(|(date, paths)| {
+                paths.iter().map(|path| QueuedPost {
+                    date: format_date(&date.with_timezone(&cfg.timezone)),
+                    path: path.to_string_lossy().to_string(),
+                })
+            })
+            .collect(),
+        Err(err) => bail!("Error getting lock on SiteWatcher: {:?}", err),
+    };
+
+    let status = Status {
+        updated_at: format_date(&Utc::now().with_timezone(&cfg.timezone)),
+        next: queued.first().map(|post| post.date.clone()),
+        queued,
+    };
+    std::fs::write(STATUS_FILE, serde_json::to_string_pretty(&status)?)
+        .with_context(|| format!("Failed to write `{STATUS_FILE}`"))
+}
+
+pub fn print_status() -> Result<()> {
+    if !Path::new(STATUS_FILE).exists() {
+        bail!("No `{STATUS_FILE}` found, is `emile watch` running on this website?");
+    }
+    let status: Status = serde_json::from_str(&std::fs::read_to_string(STATUS_FILE)?)
+        .with_context(|| format!("Failed to parse `{STATUS_FILE}`"))?;
+
+    println!("Status updated at {}", status.updated_at);
+    match status.next {
+        Some(next) => println!("Next publication: {next}"),
+        None => println!("No scheduled post"),
+    }
+    for post in &status.queued {
+        println!("  {} {}", post.date, post.path);
+    }
+    Ok(())
+}
@@ -5,14 +5,26 @@ use std::{
     time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use notify::RecursiveMode;
+use notify::{RecursiveMode, Watcher};
 use notify_debouncer_mini::DebouncedEvent;
 use tokio::sync::mpsc::UnboundedSender;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::{config::SiteConfig, post::extract_date, zola_build};
+use crate::{
+    config::SiteConfig,
+    events::{Event, EventSink},
+    format_date,
+    post::{after_dependency, extract_date, is_undrafted},
+    publish::{publish_post, PublishOptions},
+    zola_build,
+};
+
+const WATCHED_DIRS: [&str; 5] = ["content", "sass", "static", "templates", "themes"];
+
+const WATCH_LIMIT_HINT: &str = "The inotify watch limit is reached, raise it with \
+    `sysctl fs.inotify.max_user_watches=<N>` (add it to `/etc/sysctl.conf` to make it permanent)";
 
 #[derive(Debug)]
 pub enum SchedulerEvent {
@@ -24,6 +36,10 @@ pub enum SchedulerEvent {
 pub struct SiteWatcher {
     pub scheduled: Mutex<BTreeMap<DateTime<Utc>, Vec<PathBuf>>>,
     pub index: Mutex<BTreeMap<PathBuf, DateTime<Utc>>>,
+    // only log what would be done instead of building and publishing
+    pub dry_run: bool,
+    // where to report the builds and publications, for other programs to follow
+    pub events: Option<EventSink>,
 }
 
 impl SiteWatcher {
@@ -41,17 +57,18 @@ impl SiteWatcher {
             let path = entry?.path();
             if path.is_file() {
                 let file_name = path.file_name().expect("file with no name");
-                if file_name == "_index.md" {
+                if !cfg.is_post(&path) {
+                    continue;
+                }
+                if is_waiting(&path) {
+                    info!("{file_name:?} waits for another post to be published");
                     continue;
                 }
                 let date = extract_date(&path, cfg)
                     .with_context(|| format!("error extracting date from {file_name:?}"))?
                     .to_utc();
                 let file_name = PathBuf::from(file_name);
-                scheduled
-                    .entry(date)
-                    .and_modify(|e| e.push(file_name.clone()))
-                    .or_insert_with(|| vec![file_name.clone()]);
+                add_scheduled(&mut scheduled, date, file_name.clone());
                 index.insert(file_name, date);
             }
         }
@@ -59,8 +76,46 @@ impl SiteWatcher {
         Ok(Self {
             scheduled: Mutex::new(scheduled),
             index: Mutex::new(index),
+            dry_run: false,
+            events: None,
         })
     }
+
+    pub fn emit(&self, event: Event) {
+        if let Some(events) = &self.events {
+            events.emit(&event);
+        }
+    }
+
+    // Report the next publication date and the number of scheduled posts
+    pub fn emit_scheduled_changed(&self, cfg: &SiteConfig) {
+        if self.events.is_none() {
+            return;
+        }
+        match self.scheduled.lock() {
+            Ok(scheduled) => self.emit(Event::ScheduledChanged {
+                next: scheduled
+                    .keys()
+                    .next()
+                    .map(|date| format_date(&date.with_timezone(&cfg.timezone))),
+                count: scheduled.values().map(Vec::len).sum(),
+            }),
+            Err(err) => error!("Error getting lock on SiteWatcher: {:?}", err),
+        }
+    }
+}
+
+// Posts scheduled at the same date are kept sorted by file name, so they are published in the same
+// order whatever the order they were read in, when their `weight` doesn't decide
+pub fn add_scheduled(
+    scheduled: &mut BTreeMap<DateTime<Utc>, Vec<PathBuf>>,
+    date: DateTime<Utc>,
+    file_name: PathBuf,
+) {
+    let paths = scheduled.entry(date).or_default();
+    if let Err(pos) = paths.binary_search(&file_name) {
+        paths.insert(pos, file_name);
+    }
 }
 
 pub async fn start_watching(
@@ -74,30 +129,16 @@ pub async fn start_watching(
     let mut debouncer =
         notify_debouncer_mini::new_debouncer(Duration::from_secs(cfg.debouncing), tx)
             .with_context(|| "Failed to create watcher")?;
-    let watcher = debouncer.watcher();
 
     let current_dir = std::env::current_dir().with_context(|| "Failed to get current dir")?;
 
-    let dir = current_dir.join("content");
-    watcher
-        .watch(&dir, RecursiveMode::Recursive)
-        .with_context(|| format!("Failed to start watching on `{dir:?}`"))?;
-    let dir = current_dir.join("sass");
-    watcher
-        .watch(&dir, RecursiveMode::Recursive)
-        .with_context(|| format!("Failed to start watching on `{dir:?}`"))?;
-    let dir = current_dir.join("static");
-    watcher
-        .watch(&dir, RecursiveMode::Recursive)
-        .with_context(|| format!("Failed to start watching on `{dir:?}`"))?;
-    let dir = current_dir.join("templates");
-    watcher
-        .watch(&dir, RecursiveMode::Recursive)
-        .with_context(|| format!("Failed to start watching on `{dir:?}`"))?;
-    let dir = current_dir.join("themes");
-    watcher
-        .watch(&dir, RecursiveMode::Recursive)
-        .with_context(|| format!("Failed to start watching on `{dir:?}`"))?;
+    let watched_dirs: Vec<PathBuf> = WATCHED_DIRS
+        .iter()
+        .map(|dir| current_dir.join(dir))
+        .collect();
+    for dir in &watched_dirs {
+        watch_dir(debouncer.watcher(), dir)?;
+    }
 
     let schedule_abs_dir = current_dir.join(&cfg.schedule_dir);
     let draft_abs_creation_dir = current_dir.join(&cfg.drafts_creation_dir);
@@ -106,11 +147,27 @@ pub async fn start_watching(
         drafts_creation_dir: draft_abs_creation_dir,
         drafts_year_shift: cfg.drafts_year_shift,
         draft_template: cfg.draft_template.clone(),
+        draft_templates: cfg.draft_templates.clone(),
+        templates_dir: cfg.templates_dir.clone(),
         publish_dest: cfg.publish_dest.clone(),
+        lang_publish_dest: cfg.lang_publish_dest.clone(),
         schedule_dir: schedule_abs_dir,
         timezone: cfg.timezone,
         default_sch_time: cfg.default_sch_time,
         debouncing: cfg.debouncing,
+        set_updated_on_publish: cfg.set_updated_on_publish,
+        publish_draft_behavior: cfg.publish_draft_behavior,
+        min_schedule_lead_secs: cfg.min_schedule_lead_secs,
+        markdown_extensions: cfg.markdown_extensions.clone(),
+        catchup_limit: cfg.catchup_limit,
+        catchup_spacing_secs: cfg.catchup_spacing_secs,
+        past_schedule_grace_secs: cfg.past_schedule_grace_secs,
+        build_command: cfg.build_command.clone(),
+        publish_on_draft_false: cfg.publish_on_draft_false,
+        schedule_backups: cfg.schedule_backups,
+        schedule_as_draft: cfg.schedule_as_draft,
+        required_front_fields: cfg.required_front_fields.clone(),
+        date_format: cfg.date_format.clone(),
         social: cfg.social.clone(),
     };
 
@@ -120,15 +177,55 @@ pub async fn start_watching(
         match res_evt {
             Ok(evts) => {
                 for evt in evts {
+                    // the watched directory itself changed, it may have been replaced (ex: by git)
+                    if let Some(dir) = watched_dirs.iter().find(|dir| **dir == evt.path) {
+                        rewatch_dir(debouncer.watcher(), dir);
+                    }
                     process_evt(evt, s.clone(), &cfg_abs, &cfg, &tx_scheduler).await;
                 }
             }
-            Err(err) => error!("watch error: {:?}", err),
+            Err(err) => {
+                error!("watch error: {:?}", err);
+                if is_watch_limit(&err) {
+                    error!("{WATCH_LIMIT_HINT}");
+                }
+                for dir in &watched_dirs {
+                    rewatch_dir(debouncer.watcher(), dir);
+                }
+            }
         }
     }
     Ok(())
 }
 
+fn is_watch_limit(err: &notify::Error) -> bool {
+    match &err.kind {
+        notify::ErrorKind::MaxFilesWatch => true,
+        // ENOSPC is how inotify reports its watch limit
+        notify::ErrorKind::Io(e) => e.raw_os_error() == Some(28),
+        _ => false,
+    }
+}
+
+fn watch_dir(watcher: &mut dyn Watcher, dir: &Path) -> Result<()> {
+    match watcher.watch(dir, RecursiveMode::Recursive) {
+        Ok(_) => Ok(()),
+        Err(err) if is_watch_limit(&err) => {
+            bail!("Failed to start watching on `{dir:?}`: {err}. {WATCH_LIMIT_HINT}")
+        }
+        Err(err) => Err(err).with_context(|| format!("Failed to start watching on `{dir:?}`")),
+    }
+}
+
+fn rewatch_dir(watcher: &mut dyn Watcher, dir: &Path) {
+    // the old watch may already be gone, so failing to remove it is expected
+    let _ = watcher.unwatch(dir);
+    match watch_dir(watcher, dir) {
+        Ok(_) => warn!("Watch on `{dir:?}` may have been lost, re-established it"),
+        Err(err) => error!("Watch on `{dir:?}` lost and could not be re-established: {err:#}"),
+    }
+}
+
 async fn process_evt(
     evt: DebouncedEvent,
     s: Arc<SiteWatcher>,
@@ -149,12 +246,18 @@ async fn process_evt(
             return;
         }
 
+        if path.exists() && !cfg.is_post(path) {
+            return;
+        }
+
         process_schedule_evt(path, s, cfg);
         if let Err(e) = tx_scheduler.send(SchedulerEvent::Changed) {
             error!("Error sending ScheduleEvent: {:?}", e)
         }
-    } else if path.starts_with(&cfg_abs.drafts_creation_dir) {
-        // nothing to do
+    } else if let Ok(rel) = path.strip_prefix(&cfg_abs.drafts_creation_dir) {
+        if cfg.publish_on_draft_false {
+            publish_undrafted(&cfg.drafts_creation_dir.join(rel), &s, cfg).await;
+        }
     } else {
         // ignore rsync temp files
         if path
@@ -165,18 +268,82 @@ async fn process_evt(
             return;
         }
 
-        match zola_build() {
-            Ok(_) => info!("Build success after filesystem event ({:?})", evt),
-            Err(err) => error!(
-                "Failed building after filesystem event `{:?}`: {}",
-                evt, err
-            ),
+        if s.dry_run {
+            info!("Dry run: would build after filesystem event ({:?})", evt);
+            return;
+        }
+
+        s.emit(Event::BuildStarted);
+        match zola_build(cfg) {
+            Ok(_) => {
+                info!("Build success after filesystem event ({:?})", evt);
+                s.emit(Event::BuildSucceeded);
+            }
+            Err(err) => {
+                error!(
+                    "Failed building after filesystem event `{:?}`: {}",
+                    evt, err
+                );
+                s.emit(Event::BuildFailed {
+                    error: format!("{err:#}"),
+                });
+            }
+        }
+    }
+}
+
+// Publish `draft` if its `draft` flag was set to false, `draft` being relative to the website's
+// directory as `publish_post` expects
+async fn publish_undrafted(draft: &Path, s: &SiteWatcher, cfg: &SiteConfig) {
+    let is_hidden = draft
+        .file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(true);
+    // a published post must not be published again, if published in the drafts directory
+    if is_hidden
+        || !draft.is_file()
+        || !cfg.is_post(draft)
+        || cfg.publish_dirs().any(|dir| draft.starts_with(dir))
+    {
+        return;
+    }
+    match std::fs::read_to_string(draft) {
+        Ok(content) if is_undrafted(&content) => {}
+        _ => return,
+    }
+
+    if s.dry_run {
+        info!(
+            "Dry run: would publish undrafted `{}`",
+            draft.to_string_lossy()
+        );
+        return;
+    }
+    match publish_post(draft, cfg, &PublishOptions::default()).await {
+        Ok(dest) => {
+            info!("Undrafted post published: {dest}");
+            s.emit(Event::PostPublished {
+                path: draft.to_string_lossy().to_string(),
+                dest,
+            });
         }
+        Err(err) => error!(
+            "Error while publishing undrafted `{}`: {err:#}",
+            draft.to_string_lossy()
+        ),
     }
 }
 
+// scheduled with `schedule --after` and the post it depends on isn't published yet
+fn is_waiting(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| after_dependency(&content).is_some())
+        .unwrap_or(false)
+}
+
 fn process_schedule_evt(path: &Path, s: Arc<SiteWatcher>, cfg: &SiteConfig) {
-    match path.exists() {
+    // a waiting post is handled as if it were not in the schedule directory
+    match path.exists() && !is_waiting(path) {
         true => match extract_date(path, cfg) {
             Ok(date) => {
                 info!("Process file modification: {:?}", path);
@@ -207,19 +374,13 @@ fn process_schedule_evt(path: &Path, s: Arc<SiteWatcher>, cfg: &SiteConfig) {
                                     scheduled.remove(old_date);
                                 }
 
-                                scheduled
-                                    .entry(date)
-                                    .and_modify(|v| v.push(file_name.clone()))
-                                    .or_insert_with(|| vec![file_name.clone()]);
+                                add_scheduled(&mut scheduled, date, file_name.clone());
                                 *old_date = date;
                             })
                             .or_insert_with(|| {
                                 debug!("path not scheduled, add it");
                                 // not already scheduled, add it
-                                scheduled
-                                    .entry(date)
-                                    .and_modify(|v| v.push(file_name.clone()))
-                                    .or_insert_with(|| vec![file_name]);
+                                add_scheduled(&mut scheduled, date, file_name);
                                 date
                             });
                     }
@@ -237,9 +398,12 @@ fn process_schedule_evt(path: &Path, s: Arc<SiteWatcher>, cfg: &SiteConfig) {
                 (Ok(mut index), Ok(mut scheduled)) => {
                     if let Some(date) = index.remove(&file_name) {
                         info!("Unschedule {}", path.to_string_lossy());
-                        scheduled
-                            .entry(date)
-                            .and_modify(|v| v.retain(|p| p != &file_name));
+                        if let Some(paths) = scheduled.get_mut(&date) {
+                            paths.retain(|p| p != &file_name);
+                            if paths.is_empty() {
+                                scheduled.remove(&date);
+                            }
+                        }
                     }
                 }
                 _ => {
@@ -249,3 +413,29 @@ fn process_schedule_evt(path: &Path, s: Arc<SiteWatcher>, cfg: &SiteConfig) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::SiteConfig, test_dir};
+
+    use super::SiteWatcher;
+
+    #[test]
+    fn test_same_date_order() {
+        let dir = test_dir();
+        let root = dir.path();
+        let cfg = SiteConfig {
+            schedule_dir: root.to_path_buf(),
+            ..Default::default()
+        };
+        let content = "+++\ntitle = \"Post\"\ndate = 2024-06-27T12:00:00+00:00\n+++\n";
+        for name in ["b-post.md", "c-post.md", "a-post.md"] {
+            std::fs::write(root.join(name), content).unwrap();
+        }
+
+        let watcher = SiteWatcher::new(&cfg).unwrap();
+        let scheduled = watcher.scheduled.into_inner().unwrap();
+        let paths: Vec<_> = scheduled.values().flatten().collect();
+        assert_eq!(paths, ["a-post.md", "b-post.md", "c-post.md"]);
+    }
+}